@@ -0,0 +1,27 @@
+mod novimem;
+
+pub use crate::novimem::{
+    backend::{
+        core_file::CoreFileBackend,
+        dump_dir::{DumpDirBackend, DumpEntry, DumpManifest},
+        mock::MockBackend,
+        proc_mem::ProcMemBackend,
+        process_vm::ProcessVmBackend,
+        MemoryBackend,
+    },
+    cheat_table::{AddressExpr, CheatEntry, CheatTable},
+    config::{Config, CONFIG_FILE},
+    error::{NoviMemError, Result},
+    freeze::Frozen,
+    hex_dump::{DumpView, Endian, HexDump},
+    mem_image::MemImage,
+    pattern::Pattern,
+    pointer_scan::{MapRegion, Module, PointerChain, PointerMap, PointerScanOptions},
+    proc_search::ProcSearch,
+    region_filter::{Backing, RegionFilter},
+    result_set::ResultSet,
+    snapshot::SnapshotOptions,
+    text::{Encoding, TextSearch},
+    value::{DataType, FloatMatch, Value, ValueMatch},
+    MemRegion, NoviMem, RegexHit, RegionChanges, SearchResult, SearchType,
+};
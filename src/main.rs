@@ -1,18 +1,338 @@
-mod novimem;
-use novimem::{mem_image::MemImage, proc_search::ProcSearch, NoviMem, SearchType};
+use novimem::{
+    AddressExpr, Backing, CheatEntry, Config, CoreFileBackend, DataType, DumpDirBackend, DumpView,
+    Encoding, Endian, HexDump, MemImage, NoviMem, NoviMemError, Pattern, PointerMap,
+    PointerScanOptions, ProcSearch, ProcessVmBackend, RegionFilter, Result, SearchType,
+    SnapshotOptions, TextSearch, Value, ValueMatch, CONFIG_FILE,
+};
+use std::env;
 use std::io::{stdin, stdout, Write};
-use std::{env, f32, i32, mem::size_of, u8};
+use std::mem::size_of;
+use std::path::Path;
+use std::time::Duration;
+
+/// Report results the command's region refresh dropped, if it got as far as one
+fn report_dropped(mem: &mut NoviMem) {
+    let dropped = mem.take_region_changes().dropped_results;
+    if dropped > 0 {
+        println!("Dropped {} results in regions that were unmapped", dropped);
+    }
+}
+
+fn report_results(mem: &mut NoviMem, res: Result<usize>) {
+    report_dropped(mem);
+    match res {
+        Ok(num_results) => {
+            println!(
+                "Found {} {}",
+                num_results,
+                if num_results > 1 { "results" } else { "result" }
+            );
+            if num_results <= 10 {
+                mem.print_results();
+            }
+        }
+        Err(e) => println!("ERR: {}", e),
+    }
+}
 
 fn do_search(mem: &mut NoviMem, val: &[u8]) {
-    let num_results = mem.search(val);
-    println!(
-        "Found {} {}",
-        num_results,
-        if num_results > 1 { "results" } else { "result" }
-    );
-    if num_results <= 10 {
-        mem.print_results();
+    let res = mem.search(val);
+    report_results(mem, res);
+}
+
+/// Parse the arguments of `init [type] [alignment]`
+fn parse_init(parsed: &mut Vec<&str>) -> Result<(DataType, Option<usize>)> {
+    let dtype = match parsed.pop() {
+        Some(name) => DataType::parse(name)
+            .ok_or_else(|| NoviMemError::Parse(format!("unknown type '{}'", name)))?,
+        None => DataType::U8,
+    };
+    let align = match parsed.pop() {
+        Some(a) => Some(parse_alignment(a)?),
+        None => None,
+    };
+    Ok((dtype, align))
+}
+
+fn parse_alignment(a: &str) -> Result<usize> {
+    a.parse::<usize>()
+        .map_err(|_| NoviMemError::Parse(format!("'{}' is not a valid alignment", a)))
+}
+
+/// Parse the arguments of a snapshot comparison command:
+/// `ch|uch|inc|dec`, `incby|decby|chby <n>` or `chbetween <a> <b>`,
+/// with amounts in the type chosen at `init`
+fn parse_snapshot_search(cmd: &str, parsed: &mut Vec<&str>, dtype: DataType) -> Result<SearchType> {
+    let mut delta = || match parsed.pop() {
+        Some(n) => dtype.parse_delta(n),
+        None => Err(NoviMemError::Parse(
+            "additional arguments required (amount)".to_string(),
+        )),
+    };
+    Ok(match cmd {
+        "ch" => SearchType::Changed,
+        "uch" => SearchType::Unchanged,
+        "inc" => SearchType::Increased,
+        "dec" => SearchType::Decreased,
+        "incby" => SearchType::IncreasedBy(delta()?),
+        "decby" => SearchType::DecreasedBy(delta()?),
+        "chby" => SearchType::ChangedByAtLeast(delta()?),
+        _ => {
+            let (a, b) = (delta()?, delta()?);
+            if a > b {
+                SearchType::Between(b, a)
+            } else {
+                SearchType::Between(a, b)
+            }
+        }
+    })
+}
+
+/// Parse `str [-i] [-z] [-e utf8|utf16le|utf16be] <text...>`:
+/// -i ignores case, -z requires a terminating NUL, -e picks the encoding (default utf8)
+fn parse_text_search(parsed: &mut Vec<&str>) -> Result<TextSearch> {
+    let mut search = TextSearch::new("", Encoding::Utf8);
+    while let Some(flag) = parsed.last().filter(|a| a.starts_with('-')) {
+        match *flag {
+            "-i" => search.case_insensitive = true,
+            "-z" => search.null_terminated = true,
+            "-e" => {
+                parsed.pop();
+                let name = parsed.last().copied().unwrap_or_default();
+                search.encoding = Encoding::parse(name)
+                    .ok_or_else(|| NoviMemError::Parse(format!("unknown encoding '{}'", name)))?;
+            }
+            _ => break,
+        }
+        parsed.pop();
     }
+    parsed.reverse();
+    search.text = parsed.join(" ");
+    Ok(search)
+}
+
+/// Parse the optional `[depth] [max offset]` of `ptrscan`, the offset in hex
+fn parse_pointer_scan(parsed: &mut Vec<&str>) -> Result<PointerScanOptions> {
+    let mut opts = PointerScanOptions::default();
+    if let Some(depth) = parsed.pop() {
+        opts.max_depth = depth
+            .parse::<usize>()
+            .map_err(|_| NoviMemError::Parse(format!("'{}' is not a valid depth", depth)))?;
+    }
+    if let Some(offset) = parsed.pop() {
+        opts.max_offset = u64::from_str_radix(&offset.replace("0x", ""), 16)
+            .map_err(|_| NoviMemError::Parse(format!("'{}' is not a valid offset", offset)))?;
+    }
+    Ok(opts)
+}
+
+/// Load the `<map file> <target addr>` pairs of `ptrcheck`
+fn parse_pointer_maps(parsed: &mut Vec<&str>) -> Result<Vec<(PointerMap, u64)>> {
+    let mut maps = Vec::new();
+    while let Some(file) = parsed.pop() {
+        let addr = parsed.pop().ok_or_else(|| {
+            NoviMemError::Parse(format!(
+                "additional arguments required (address for {})",
+                file
+            ))
+        })?;
+        let addr = u64::from_str_radix(&addr.replace("0x", ""), 16)
+            .map_err(|_| NoviMemError::Parse(format!("'{}' is not a valid address", addr)))?;
+        maps.push((PointerMap::load(Path::new(file))?, addr));
+    }
+    Ok(maps)
+}
+
+fn print_pointer_chains(mem: &NoviMem) {
+    println!("{} pointer chains", mem.pointer_chains().len());
+    for chain in mem.pointer_chains().iter().take(20) {
+        match mem.resolve_chain(chain) {
+            Ok(addr) => println!("{}\t= {:X}", chain, addr),
+            Err(e) => println!("{}\t({})", chain, e),
+        }
+    }
+}
+
+/// Parse `freeze <type> <addr> [value]`; without a value the current one is kept
+fn parse_freeze(parsed: &mut Vec<&str>, mem: &NoviMem) -> Result<(u64, DataType, Value)> {
+    let name = parsed.pop().unwrap_or_default();
+    let dtype = DataType::parse(name)
+        .ok_or_else(|| NoviMemError::Parse(format!("unknown type '{}'", name)))?;
+    let addr = get_addr(parsed, mem)
+        .ok_or_else(|| NoviMemError::Parse("no address to freeze".to_string()))?;
+    let value = match parsed.pop() {
+        Some(v) => dtype.parse_value(v)?,
+        None => dtype.decode(&mem.getval(addr, dtype.size())?),
+    };
+    Ok((addr, dtype, value))
+}
+
+/// Parse the address of `tadd`: `#<n>` is result n, `*` every result, `@<n>` pointer chain n,
+/// anything else an address expression like `game+10->+8`
+fn parse_table_addresses(arg: &str, mem: &NoviMem) -> Result<Vec<AddressExpr>> {
+    let index = |s: &str, len: usize| {
+        s.parse::<usize>()
+            .ok()
+            .filter(|i| *i < len)
+            .ok_or_else(|| NoviMemError::Parse(format!("no entry {}", s)))
+    };
+    if arg == "*" {
+        Ok(mem.results().iter().map(|a| mem.address_expr(a)).collect())
+    } else if let Some(n) = arg.strip_prefix('#') {
+        let idx = index(n, mem.results().len())?;
+        Ok(vec![mem.address_expr(mem.results().get(idx).unwrap())])
+    } else if let Some(n) = arg.strip_prefix('@') {
+        let chains = mem.pointer_chains();
+        Ok(vec![AddressExpr::Pointer(
+            chains[index(n, chains.len())?].clone(),
+        )])
+    } else {
+        Ok(vec![AddressExpr::parse(arg)?])
+    }
+}
+
+/// Parse `tadd <type> <address> [description...]`
+fn parse_cheat_entries(parsed: &mut Vec<&str>, mem: &NoviMem) -> Result<Vec<CheatEntry>> {
+    let name = parsed.pop().unwrap_or_default();
+    let dtype = DataType::parse(name)
+        .ok_or_else(|| NoviMemError::Parse(format!("unknown type '{}'", name)))?;
+    let addresses = parse_table_addresses(parsed.pop().unwrap_or_default(), mem)?;
+    parsed.reverse();
+    let description = parsed.join(" ");
+    Ok(addresses
+        .into_iter()
+        .map(|address| CheatEntry {
+            description: description.clone(),
+            dtype,
+            address,
+            frozen: None,
+            group: None,
+        })
+        .collect())
+}
+
+/// The value of `tfreeze <entry> [value]`, the entry's current value if none is given
+fn parse_cheat_freeze(args: &[&str], mem: &NoviMem, idx: usize) -> Result<Value> {
+    match args.first() {
+        Some(v) => mem.cheat_table().entries[idx].dtype.parse_value(v),
+        None => mem.cheat_values().remove(idx).map(|(_, v)| v),
+    }
+}
+
+/// Parse the flags of `dump [-w 1|2|4|8] [-be] [-t type | -p] <addr> [len]`:
+/// -w groups bytes into words, -be reads them big-endian, -t shows values of a type
+/// and -p one pointer per row
+fn parse_dump(parsed: &mut Vec<&str>) -> Result<HexDump> {
+    let mut dump = HexDump::default();
+    while let Some(flag) = parsed.last().filter(|a| a.starts_with('-')) {
+        match *flag {
+            "-w" => {
+                parsed.pop();
+                let size = parsed.last().copied().unwrap_or_default();
+                dump.set_word_size(size.parse::<usize>().map_err(|_| {
+                    NoviMemError::Parse(format!("'{}' is not a valid word size", size))
+                })?)?;
+            }
+            "-be" => dump.endian = Endian::Big,
+            "-t" => {
+                parsed.pop();
+                let name = parsed.last().copied().unwrap_or_default();
+                dump.view = DumpView::Typed(
+                    DataType::parse(name)
+                        .ok_or_else(|| NoviMemError::Parse(format!("unknown type '{}'", name)))?,
+                );
+            }
+            "-p" => dump.view = DumpView::Pointer,
+            _ => break,
+        }
+        parsed.pop();
+    }
+    Ok(dump)
+}
+
+/// Apply `filter <setting> [args...]` to the region filter:
+/// `perms rw-p`, `include|exclude [patterns...]`, `size <min|-> <max|->`,
+/// `backing any|file|anon`, `reset`, or `save` to keep it in the config file
+fn update_filter(parsed: &mut Vec<&str>, mem: &mut NoviMem) -> Result<()> {
+    let mut filter = mem.region_filter().clone();
+    let setting = match parsed.pop() {
+        Some(setting) => setting,
+        None => return Ok(()),
+    };
+    parsed.reverse();
+    let size = |s: Option<&&str>| match s {
+        None | Some(&"-") => Ok(None),
+        Some(s) => s
+            .parse::<usize>()
+            .map(Some)
+            .map_err(|_| NoviMemError::Parse(format!("'{}' is not a valid size", s))),
+    };
+    match setting {
+        "perms" => filter.perms = parsed.first().copied().unwrap_or("????").to_string(),
+        "include" => filter.include = parsed.iter().map(|p| p.to_string()).collect(),
+        "exclude" => filter.exclude = parsed.iter().map(|p| p.to_string()).collect(),
+        "size" => {
+            filter.min_size = size(parsed.first())?;
+            filter.max_size = size(parsed.get(1))?;
+        }
+        "backing" => {
+            let name = parsed.first().copied().unwrap_or_default();
+            filter.backing = Backing::parse(name)
+                .ok_or_else(|| NoviMemError::Parse(format!("unknown backing '{}'", name)))?;
+        }
+        "reset" => filter = RegionFilter::default(),
+        "save" => {
+            let path = Path::new(CONFIG_FILE);
+            let mut config = Config::load(path)?;
+            config.region_filter = filter.clone();
+            config.save(path)?;
+        }
+        _ => {
+            return Err(NoviMemError::Parse(format!(
+                "unknown filter setting '{}'",
+                setting
+            )))
+        }
+    }
+    mem.set_region_filter(filter)
+}
+
+/// Apply `snapopt <setting> [args...]` to where snapshots are kept:
+/// `budget <MiB>`, `compress on|off`, `dir <path>|-`, `reset`, or `save` to keep them
+/// in the config file
+fn update_snapshot_options(parsed: &mut Vec<&str>, mem: &mut NoviMem) -> Result<()> {
+    let mut options = mem.snapshot_options().clone();
+    let setting = match parsed.pop() {
+        Some(setting) => setting,
+        None => return Ok(()),
+    };
+    match (setting, parsed.pop()) {
+        ("budget", Some(mib)) => {
+            let mib = mib
+                .parse::<usize>()
+                .map_err(|_| NoviMemError::Parse(format!("'{}' is not a valid size", mib)))?;
+            options.memory_budget = mib << 20;
+        }
+        ("compress", Some("on")) => options.compress = true,
+        ("compress", Some("off")) => options.compress = false,
+        ("dir", Some("-")) => options.spill_dir = None,
+        ("dir", Some(dir)) => options.spill_dir = Some(dir.into()),
+        ("reset", _) => options = SnapshotOptions::default(),
+        ("save", _) => {
+            let path = Path::new(CONFIG_FILE);
+            let mut config = Config::load(path)?;
+            config.snapshots = options.clone();
+            config.save(path)?;
+        }
+        _ => {
+            return Err(NoviMemError::Parse(format!(
+                "unknown snapshot setting '{}'",
+                setting
+            )))
+        }
+    }
+    mem.set_snapshot_options(options);
+    Ok(())
 }
 
 fn get_addr(parsed: &mut Vec<&str>, mem: &NoviMem) -> Option<u64> {
@@ -24,7 +344,7 @@ fn get_addr(parsed: &mut Vec<&str>, mem: &NoviMem) -> Option<u64> {
             None
         }
     } else if mem.results().len() == 1 {
-        Some(mem.results()[0])
+        mem.results().get(0)
     } else {
         println!("Additional arguments required (address)");
         None
@@ -34,13 +354,14 @@ fn get_addr(parsed: &mut Vec<&str>, mem: &NoviMem) -> Option<u64> {
 macro_rules! readval {
     ($type: ty, $parsed: ident, $mem: ident) => {
         if let Some(addr) = get_addr(&mut $parsed, $mem) {
-            if let Some(val) = $mem.getval(addr, size_of::<$type>()) {
-                // TODO: Is there a cleaner way to do this? (slice to fixed size array)
-                let mut arr = [0u8; size_of::<$type>()];
-                arr.copy_from_slice(&val[..size_of::<$type>()]);
-                println!("{}", <$type>::from_le_bytes(arr));
-            } else {
-                println!("Unable read value at address {:X}", addr);
+            match $mem.getval(addr, size_of::<$type>()) {
+                Ok(val) => {
+                    // TODO: Is there a cleaner way to do this? (slice to fixed size array)
+                    let mut arr = [0u8; size_of::<$type>()];
+                    arr.copy_from_slice(&val[..size_of::<$type>()]);
+                    println!("{}", <$type>::from_le_bytes(arr));
+                }
+                Err(e) => println!("Unable read value at address {:X}: {}", addr, e),
             }
         }
     };
@@ -51,7 +372,9 @@ macro_rules! writeval {
         if let Some(addr) = get_addr(&mut $parsed, $mem) {
             if let Some(val_str) = $parsed.pop() {
                 if let Ok(val) = val_str.parse::<$type>() {
-                    $mem.setval(addr, &val.to_le_bytes());
+                    if let Err(e) = $mem.setval(addr, &val.to_le_bytes()) {
+                        println!("Unable to write value at address {:X}: {}", addr, e);
+                    }
                 } else {
                     println!("Unable to parse {} as value", val_str);
                 }
@@ -65,7 +388,7 @@ macro_rules! writeval {
 macro_rules! search_num {
     ($type: ty, $parsed: ident, $mem: ident) => {
         if let Some(mut search_str) = $parsed.pop() {
-            let radix = if &search_str[0..2] == "0x" {
+            let radix = if search_str.starts_with("0x") {
                 search_str = &search_str[2..];
                 16
             } else {
@@ -108,31 +431,37 @@ fn interactive(mem: &mut NoviMem) {
                 let mut parsed: Vec<&str> = input[..n - 1].split(' ').collect();
                 parsed.reverse();
                 if let Some(cmd) = parsed.pop() {
+                    // Only changes found by this command's own refresh get reported
+                    mem.take_region_changes();
                     match cmd {
                         "init" => {
-                            mem.take_snapshots(None);
-                        }
-                        "uch" => {
-                            let num_results = mem.take_snapshots(Some(SearchType::Unchanged));
-                            println!(
-                                "Found {} {}",
-                                num_results,
-                                if num_results > 1 { "results" } else { "result" }
-                            );
-                            if num_results <= 10 {
-                                mem.print_results();
+                            match parse_init(&mut parsed).and_then(|(dtype, align)| {
+                                let align =
+                                    align.unwrap_or_else(|| mem.alignment_for(dtype.size()));
+                                mem.init_snapshots(dtype, align)
+                            }) {
+                                Ok(_) => {
+                                    let (dtype, align) = mem.scan_type();
+                                    println!("Snapshot taken ({}, alignment {})", dtype, align);
+                                }
+                                Err(e) => println!("ERR: {}", e),
                             }
                         }
-                        "ch" => {
-                            let num_results = mem.take_snapshots(Some(SearchType::Changed));
-                            println!(
-                                "Found {} {}",
-                                num_results,
-                                if num_results > 1 { "results" } else { "result" }
-                            );
-                            if num_results <= 10 {
-                                mem.print_results();
-                            }
+                        "ch" | "uch" | "inc" | "dec" | "incby" | "decby" | "chby" | "chbetween" => {
+                            let res = parse_snapshot_search(cmd, &mut parsed, mem.scan_type().0)
+                                .and_then(|stype| mem.take_snapshots(Some(stype)));
+                            report_results(mem, res);
+                        }
+                        // Range searches: "<type> between <a> <b>" or "<type> <op> <value>"
+                        _ if DataType::parse(cmd).is_some()
+                            && parsed.last().is_some_and(|op| ValueMatch::is_operator(op)) =>
+                        {
+                            let dtype = DataType::parse(cmd).unwrap();
+                            let op = parsed.pop().unwrap();
+                            parsed.reverse();
+                            let res = ValueMatch::parse(dtype, op, &parsed)
+                                .and_then(|vmatch| mem.search_value(dtype, &vmatch));
+                            report_results(mem, res);
                         }
                         "b" => search_num!(u8, parsed, mem),
                         "i8" => search_num!(i8, parsed, mem),
@@ -149,20 +478,295 @@ fn interactive(mem: &mut NoviMem) {
                         "u64" => search_num!(u64, parsed, mem),
                         "f" => search_float!(f32, parsed, mem),
                         "f64" => search_float!(f64, parsed, mem),
+                        "align" => {
+                            let res = match parsed.pop() {
+                                Some("auto") => mem.set_alignment(None),
+                                Some(a) => {
+                                    parse_alignment(a).and_then(|a| mem.set_alignment(Some(a)))
+                                }
+                                None => Ok(()),
+                            };
+                            match (res, mem.alignment()) {
+                                (Err(e), _) => println!("ERR: {}", e),
+                                (Ok(_), Some(a)) => println!("Alignment: {}", a),
+                                (Ok(_), None) => println!("Alignment: natural for the type"),
+                            }
+                        }
+                        "aob" => {
+                            parsed.reverse();
+                            let align = mem.alignment().unwrap_or(1);
+                            let res = Pattern::parse(&parsed.join(" "))
+                                .and_then(|pattern| mem.search_pattern(&pattern, align));
+                            report_results(mem, res);
+                        }
+                        "str" => {
+                            let res = parse_text_search(&mut parsed)
+                                .and_then(|search| mem.search_text(&search));
+                            report_results(mem, res);
+                        }
+                        // Raw regexes over memory, as UTF-8 text or as bytes
+                        "regex" | "bregex" => {
+                            parsed.reverse();
+                            let res = mem.search_regex(&parsed.join(" "), cmd == "regex");
+                            report_results(mem, res);
+                        }
+                        // Pointer chains: "ptrscan <addr> [depth] [max offset]"
+                        "ptrscan" => {
+                            if let Some(addr) = get_addr(&mut parsed, mem) {
+                                let res = parse_pointer_scan(&mut parsed)
+                                    .and_then(|opts| mem.pointer_scan(addr, &opts).map(|_| ()));
+                                report_dropped(mem);
+                                match res {
+                                    Ok(_) => print_pointer_chains(mem),
+                                    Err(e) => println!("ERR: {}", e),
+                                }
+                            }
+                        }
+                        // Keep the chains that still lead to the value, e.g. after a restart
+                        "ptrrescan" => {
+                            let target = if parsed.is_empty() {
+                                None
+                            } else {
+                                match get_addr(&mut parsed, mem) {
+                                    Some(addr) => Some(addr),
+                                    None => continue,
+                                }
+                            };
+                            mem.rescan_pointer_chains(target);
+                            print_pointer_chains(mem);
+                        }
+                        "ptrs" => print_pointer_chains(mem),
+                        // Save every pointer of this run, to check chains against later
+                        "ptrmap" => {
+                            if let Some(file) = parsed.pop() {
+                                let res = mem
+                                    .pointer_map(PointerScanOptions::default().alignment)
+                                    .and_then(|map| {
+                                        map.save(Path::new(file)).map(|_| map.pointers.len())
+                                    });
+                                report_dropped(mem);
+                                match res {
+                                    Ok(n) => println!("Saved {} pointers to {}", n, file),
+                                    Err(e) => println!("Unable to save pointer map: {}", e),
+                                }
+                            } else {
+                                println!("Additional arguments required (file)");
+                            }
+                        }
+                        // Keep the chains that reach the value in other runs:
+                        // "ptrcheck <map file> <addr> [<map file> <addr>...]"
+                        "ptrcheck" => match parse_pointer_maps(&mut parsed) {
+                            Ok(maps) => {
+                                mem.check_pointer_chains(&maps);
+                                print_pointer_chains(mem);
+                            }
+                            Err(e) => println!("ERR: {}", e),
+                        },
+                        "ptrsave" => match mem.save_pointer_chains() {
+                            Ok(_) => {
+                                println!("Saved {} pointer chains", mem.pointer_chains().len())
+                            }
+                            Err(e) => println!("Unable to save pointer chains: {}", e),
+                        },
+                        "ptrload" => match mem.load_pointer_chains() {
+                            Ok(n) => println!("Loaded {} pointer chains", n),
+                            Err(e) => println!("Unable to load pointer chains: {}", e),
+                        },
+                        // Cheat table
+                        "tadd" => {
+                            match parse_cheat_entries(&mut parsed, mem).and_then(|entries| {
+                                entries.into_iter().try_for_each(|e| mem.add_cheat(e))
+                            }) {
+                                Ok(_) => mem.print_cheat_table(),
+                                Err(e) => println!("ERR: {}", e),
+                            }
+                        }
+                        "tlist" => mem.print_cheat_table(),
+                        "tdel" | "tgroup" | "tfreeze" | "tunfreeze" => {
+                            let idx = match parsed.pop().map(|n| n.parse::<usize>()) {
+                                Some(Ok(idx)) if idx < mem.cheat_table().entries.len() => idx,
+                                _ => {
+                                    println!("Additional arguments required (entry)");
+                                    continue;
+                                }
+                            };
+                            parsed.reverse();
+                            let res = match cmd {
+                                "tdel" => {
+                                    mem.remove_cheat(idx);
+                                    Ok(())
+                                }
+                                "tgroup" => {
+                                    let group = Some(parsed.join(" ")).filter(|g| !g.is_empty());
+                                    mem.set_cheat_group(idx, group);
+                                    Ok(())
+                                }
+                                "tfreeze" => parse_cheat_freeze(&parsed, mem, idx)
+                                    .and_then(|v| mem.set_cheat_frozen(idx, Some(v))),
+                                _ => mem.set_cheat_frozen(idx, None),
+                            };
+                            match res {
+                                Ok(_) => mem.print_cheat_table(),
+                                Err(e) => println!("ERR: {}", e),
+                            }
+                        }
+                        "tsave" | "tload" => {
+                            if let Some(file) = parsed.pop() {
+                                let res = if cmd == "tsave" {
+                                    mem.save_cheat_table(Path::new(file))
+                                } else {
+                                    mem.load_cheat_table(Path::new(file)).map(|failed| {
+                                        if failed > 0 {
+                                            println!(
+                                                "{} frozen entries could not be resolved",
+                                                failed
+                                            );
+                                        }
+                                        mem.print_cheat_table();
+                                    })
+                                };
+                                if let Err(e) = res {
+                                    println!("ERR: {}", e);
+                                }
+                            } else {
+                                println!("Additional arguments required (file)");
+                            }
+                        }
+                        "freeze" => {
+                            if let Err(e) = parse_freeze(&mut parsed, mem)
+                                .and_then(|(addr, dtype, value)| mem.freeze(addr, dtype, value))
+                            {
+                                println!("ERR: {}", e);
+                            }
+                        }
+                        "unfreeze" => match parsed.last() {
+                            Some(&"all") => mem.unfreeze_all(),
+                            _ => {
+                                if let Some(addr) = get_addr(&mut parsed, mem) {
+                                    if !mem.unfreeze(addr) {
+                                        println!("{:X} is not frozen", addr);
+                                    }
+                                }
+                            }
+                        },
+                        "frozen" => {
+                            for f in mem.frozen() {
+                                match f.error {
+                                    Some(e) => {
+                                        println!("{:X}\t{} {}\t({})", f.addr, f.dtype, f.value, e)
+                                    }
+                                    None => println!("{:X}\t{} {}", f.addr, f.dtype, f.value),
+                                }
+                            }
+                        }
+                        // How often frozen values are rewritten, in milliseconds
+                        "freezeint" => {
+                            if let Some(ms) = parsed.pop() {
+                                match ms.parse::<u64>() {
+                                    Ok(0) => println!("The interval must be at least 1ms"),
+                                    Ok(ms) => mem.set_freeze_interval(Duration::from_millis(ms)),
+                                    Err(_) => println!("Unable to parse {} as interval", ms),
+                                }
+                            }
+                            println!("Freeze interval: {}ms", mem.freeze_interval().as_millis());
+                        }
+                        "pause" => {
+                            match parsed.pop() {
+                                Some("on") => mem.set_pause_scans(true),
+                                Some("off") => mem.set_pause_scans(false),
+                                Some(arg) => println!("Expected 'on' or 'off', got '{}'", arg),
+                                None => {}
+                            }
+                            println!(
+                                "The process is {} during scans",
+                                if mem.pause_scans() {
+                                    "paused"
+                                } else {
+                                    "left running"
+                                }
+                            );
+                        }
+                        "refresh" => match mem.refresh_regions().map(|_| ()) {
+                            Ok(_) => {
+                                let changes = mem.take_region_changes();
+                                for r in &changes.added {
+                                    println!("+ {:X}:{:X}\t{}", r.start_addr, r.end_addr, r.name);
+                                }
+                                for r in &changes.removed {
+                                    println!("- {:X}:{:X}\t{}", r.start_addr, r.end_addr, r.name);
+                                }
+                                for (old, new) in &changes.resized {
+                                    println!(
+                                        "~ {:X}:{:X} -> {:X}\t{}",
+                                        old.start_addr, old.end_addr, new.end_addr, new.name
+                                    );
+                                }
+                                if changes.dropped_results > 0 {
+                                    println!(
+                                        "Dropped {} results in regions that were unmapped",
+                                        changes.dropped_results
+                                    );
+                                }
+                            }
+                            Err(e) => println!("ERR: {}", e),
+                        },
+                        "filter" => {
+                            if let Err(e) = update_filter(&mut parsed, mem) {
+                                println!("ERR: {}", e);
+                            }
+                            println!(
+                                "Scanning {} regions: {}",
+                                mem.scan_regions().count(),
+                                mem.region_filter()
+                            );
+                        }
+                        "snapopt" => {
+                            if let Err(e) = update_snapshot_options(&mut parsed, mem) {
+                                println!("ERR: {}", e);
+                            }
+                            let (memory, disk) = mem.snapshot_usage();
+                            println!(
+                                "Snapshots: {} ({} MiB in memory, {} MiB on disk)",
+                                mem.snapshot_options(),
+                                memory >> 20,
+                                disk >> 20
+                            );
+                        }
+                        "exec" => {
+                            match parsed.pop() {
+                                Some("on") => mem.set_include_exec(true),
+                                Some("off") => mem.set_include_exec(false),
+                                Some(arg) => println!("Expected 'on' or 'off', got '{}'", arg),
+                                None => {}
+                            }
+                            println!(
+                                "Executable regions are {}",
+                                if mem.include_exec() {
+                                    "scanned"
+                                } else {
+                                    "skipped"
+                                }
+                            );
+                        }
                         "p" => mem.print_results(),
                         "pm" => mem.print_modules(),
                         "c" => mem.clear_results(),
                         "clear" => mem.clear_results(),
                         "save" => {
                             if let Some(name) = parsed.pop() {
-                                mem.save_search(name.to_string());
-                                mem.save_searches_to_file();
+                                if let Err(e) = mem.save_search(name.to_string()) {
+                                    println!("Unable to save searches: {}", e);
+                                }
                             }
                         }
                         "restore" => {
                             if let Some(name) = parsed.pop() {
-                                if !mem.restore_search(name.to_string()) {
-                                    println!("Saved search '{}' not found", &name);
+                                match mem.restore_search(name.to_string()) {
+                                    Some(0) => {}
+                                    Some(dropped) => {
+                                        println!("{} results are no longer mapped", dropped)
+                                    }
+                                    None => println!("Saved search '{}' not found", &name),
                                 }
                             }
                         }
@@ -215,7 +819,9 @@ fn interactive(mem: &mut NoviMem) {
                                 {
                                     if let Some(size_str) = parsed.pop() {
                                         if let Ok(size) = size_str.parse::<usize>() {
-                                            m_img.print_img(mem, addr, size);
+                                            if let Err(e) = m_img.print_img(mem, addr, size) {
+                                                println!("Unable to create image: {}", e);
+                                            }
                                         } else {
                                             println!("Unable to parse {} as size", size_str);
                                         }
@@ -229,8 +835,39 @@ fn interactive(mem: &mut NoviMem) {
                                 println!("Additional arguments required (address)");
                             }
                         }
+                        "dump" => match parse_dump(&mut parsed) {
+                            Ok(dump) => {
+                                if let Some(addr) = get_addr(&mut parsed, mem) {
+                                    let len = match parsed.pop().map(|l| l.parse::<usize>()) {
+                                        Some(Ok(len)) => len,
+                                        Some(Err(_)) => {
+                                            println!("Unable to parse length");
+                                            continue;
+                                        }
+                                        None => 128,
+                                    };
+                                    match mem.hex_dump(addr, len, &dump) {
+                                        Ok(rows) => rows.iter().for_each(|r| println!("{}", r)),
+                                        Err(e) => println!("Unable to dump {:X}: {}", addr, e),
+                                    }
+                                }
+                            }
+                            Err(e) => println!("ERR: {}", e),
+                        },
+                        "capture" => {
+                            if let Some(dir) = parsed.pop() {
+                                match mem.dump_to_dir(Path::new(dir)) {
+                                    Ok(n) => println!("Dumped {} regions to {}", n, dir),
+                                    Err(e) => println!("Unable to capture to {}: {}", dir, e),
+                                }
+                            } else {
+                                println!("Additional arguments required (directory)");
+                            }
+                        }
                         "x" => {
-                            mem.save_searches_to_file();
+                            if let Err(e) = mem.save_searches_to_file() {
+                                println!("Unable to save searches: {}", e);
+                            }
                             break;
                         }
                         _ => println!("Unknown command {}", cmd),
@@ -242,76 +879,102 @@ fn interactive(mem: &mut NoviMem) {
     }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if let Some(search_str) = args.get(1) {
-        if let Some(mut procs) = ProcSearch::search(search_str) {
-            if !procs.is_empty() {
-                println!("Found {} total", procs.len());
-                let (name, pid) = if procs.len() > 1 {
-                    for (idx, (name, pid)) in procs.iter().enumerate() {
-                        println!("{}:\t{}\t{}", idx, name, pid);
-                    }
-                    print!("Choose pid:");
-                    stdout().flush().unwrap();
-                    let mut input = String::new();
-                    if let Ok(n) = stdin().read_line(&mut input) {
-                        if let Ok(choice) = usize::from_str_radix(&input[..n - 1], 10) {
-                            if choice <= procs.len() {
-                                &procs[choice]
-                            } else {
-                                panic!("Chose invalid index");
-                            }
-                        } else {
-                            panic!("Unable to parse input as int");
-                        }
-                    } else {
-                        panic! {"I/O error"};
-                    }
+fn choose_process(search_str: &str) -> Option<(String, u32)> {
+    let procs = match ProcSearch::search(search_str) {
+        Ok(procs) => procs,
+        Err(e) => {
+            println!("ERR: Unable to search processes: {}", e);
+            return None;
+        }
+    };
+    if procs.is_empty() {
+        println!("{} not found", search_str);
+        return None;
+    }
+    println!("Found {} total", procs.len());
+    let (name, pid) = if procs.len() > 1 {
+        for (idx, (name, pid)) in procs.iter().enumerate() {
+            println!("{}:\t{}\t{}", idx, name, pid);
+        }
+        print!("Choose pid:");
+        stdout().flush().unwrap();
+        let mut input = String::new();
+        if let Ok(n) = stdin().read_line(&mut input) {
+            if let Ok(choice) = input[..n - 1].parse::<usize>() {
+                if choice < procs.len() {
+                    &procs[choice]
                 } else {
-                    &procs[0]
-                };
-                // remove null chars
-                let name = name.replace('\0', "");
-                let mut m = NoviMem::new(*pid, String::from(&name));
-                println!("loaded proc {}", &name);
-                m.load_searches_from_file();
-                interactive(&mut m);
+                    println!("Chose invalid index");
+                    return None;
+                }
+            } else {
+                println!("Unable to parse input as int");
+                return None;
             }
         } else {
-            println!("{} not found", search_str);
+            println!("I/O error");
+            return None;
         }
     } else {
-        println!("ERR: Requires process name");
-    }
-    stdout().flush().unwrap();
+        &procs[0]
+    };
+    // remove null chars
+    Some((name.replace('\0', ""), *pid))
 }
 
-#[cfg(test)]
-#[allow(dead_code)]
-use std::process;
-#[test]
-fn test_search() {
-    let pid = process::id();
-    let mut m = NoviMem::new(pid, String::from("novimem"));
-    let x = 0xDEADBEEFDEADBEEFDEADBEEFDEADBEEFu128;
-    m.search(&x.to_le_bytes());
-    m.print_results();
-
-    let old_results = m.results();
-    let newval = 0xDEADC0DEDEADC0DEDEADC0DEDEADC0DEu128;
-    old_results.iter().for_each(|result| {
-        println!("Writing to address {:X}", *result);
-        // m.setval(*result, &newval.to_le_bytes());
-    });
-
-    m.clear_results();
-    m.search(&newval.to_le_bytes());
-    m.print_results();
-
-    m.results().iter().for_each(|result| {
-        assert!(old_results.contains(result));
-    });
-
-    assert!(false);
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    args.reverse();
+    let mut use_vm = false;
+    let mut dump_dir = None;
+    let mut core_file = None;
+    let mut search_str = None;
+    while let Some(arg) = args.pop() {
+        match arg.as_str() {
+            // --vm reads the target with process_vm_readv instead of /proc/<pid>/mem
+            "--vm" => use_vm = true,
+            // --dump <dir> scans a capture written by the "capture" command instead of a live process
+            "--dump" => dump_dir = args.pop(),
+            // --core <file> scans an ELF core dump of the target
+            "--core" => core_file = args.pop(),
+            _ => search_str = Some(arg),
+        }
+    }
+    let opened = if let Some(dir) = dump_dir {
+        DumpDirBackend::open(&dir).and_then(|b| {
+            let name = b.pname().to_string();
+            NoviMem::with_backend(Box::new(b), name)
+        })
+    } else if let Some(path) = core_file {
+        let name = Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        CoreFileBackend::open(&path).and_then(|b| NoviMem::with_backend(Box::new(b), name))
+    } else if let Some(search_str) = search_str {
+        match choose_process(&search_str) {
+            Some((name, pid)) if use_vm => {
+                ProcessVmBackend::new(pid).and_then(|b| NoviMem::with_backend(Box::new(b), name))
+            }
+            Some((name, pid)) => NoviMem::new(pid, name),
+            None => return,
+        }
+    } else {
+        println!("ERR: Requires process name, --dump <dir> or --core <file>");
+        return;
+    };
+    match opened {
+        Ok(mut m) => {
+            println!("loaded proc {}", m.pname());
+            if let Err(e) = Config::load(Path::new(CONFIG_FILE)).and_then(|c| c.apply(&mut m)) {
+                println!("Unable to load {}: {}", CONFIG_FILE, e);
+            }
+            if let Err(e) = m.load_searches_from_file() {
+                println!("Unable to load saved searches: {}", e);
+            }
+            interactive(&mut m);
+        }
+        Err(e) => println!("ERR: Unable to open memory: {}", e),
+    }
+    stdout().flush().unwrap();
 }
@@ -0,0 +1,372 @@
+use super::MemoryBackend;
+use crate::novimem::{
+    error::{NoviMemError, Result},
+    MemRegion,
+};
+use std::{fs::File, os::unix::fs::FileExt, path::Path};
+
+const ET_CORE: u16 = 4;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_FILE: u32 = 0x4649_4c45;
+
+struct Segment {
+    region: MemRegion,
+    file_offset: u64,
+    file_size: u64,
+}
+
+/// Serves the address space recorded in a 64-bit little-endian ELF core dump
+pub struct CoreFileBackend {
+    file: File,
+    segments: Vec<Segment>,
+}
+
+fn field<T: Copy>(buf: &[u8], off: usize, conv: fn(&[u8]) -> T, size: usize) -> Result<T> {
+    buf.get(off..off + size)
+        .map(conv)
+        .ok_or_else(|| NoviMemError::Parse(format!("core file truncated at offset {}", off)))
+}
+
+fn u16_at(buf: &[u8], off: usize) -> Result<u16> {
+    field(buf, off, |b| u16::from_le_bytes([b[0], b[1]]), 2)
+}
+
+fn u32_at(buf: &[u8], off: usize) -> Result<u32> {
+    field(
+        buf,
+        off,
+        |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        4,
+    )
+}
+
+fn u64_at(buf: &[u8], off: usize) -> Result<u64> {
+    field(
+        buf,
+        off,
+        |b| {
+            let mut arr = [0u8; 8];
+            arr.copy_from_slice(&b[..8]);
+            u64::from_le_bytes(arr)
+        },
+        8,
+    )
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+/// Fail unless `size` bytes at `offset` lie within a file of `file_len` bytes
+fn check_in_file(what: &str, offset: u64, size: u64, file_len: u64) -> Result<()> {
+    match offset.checked_add(size) {
+        Some(end) if end <= file_len => Ok(()),
+        _ => Err(NoviMemError::Parse(format!(
+            "{} runs past the end of the core file",
+            what
+        ))),
+    }
+}
+
+impl CoreFileBackend {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<CoreFileBackend> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut ehdr = [0u8; 64];
+        file.read_exact_at(&mut ehdr, 0)?;
+        if &ehdr[..4] != b"\x7fELF" || ehdr[4] != 2 || ehdr[5] != 1 {
+            return Err(NoviMemError::Parse(
+                "not a 64-bit little-endian ELF file".to_string(),
+            ));
+        }
+        if u16_at(&ehdr, 0x10)? != ET_CORE {
+            return Err(NoviMemError::Parse(
+                "ELF file is not a core dump".to_string(),
+            ));
+        }
+        let phoff = u64_at(&ehdr, 0x20)?;
+        let phentsize = u16_at(&ehdr, 0x36)? as usize;
+        let phnum = u16_at(&ehdr, 0x38)? as usize;
+        check_in_file(
+            "program header table",
+            phoff,
+            (phentsize * phnum) as u64,
+            file_len,
+        )?;
+        let mut phdrs = vec![0u8; phentsize * phnum];
+        file.read_exact_at(&mut phdrs, phoff)?;
+
+        let mut segments = Vec::new();
+        let mut mapped_files = Vec::new();
+        for i in 0..phnum {
+            let ph = &phdrs[i * phentsize..(i + 1) * phentsize];
+            let p_type = u32_at(ph, 0)?;
+            let p_flags = u32_at(ph, 4)?;
+            let p_offset = u64_at(ph, 8)?;
+            let p_vaddr = u64_at(ph, 16)?;
+            let p_filesz = u64_at(ph, 32)?;
+            let p_memsz = u64_at(ph, 40)?;
+            let p_end = p_vaddr.checked_add(p_memsz).ok_or_else(|| {
+                NoviMemError::Parse(format!("segment at {:X} wraps the address space", p_vaddr))
+            })?;
+            match p_type {
+                PT_LOAD if p_memsz > 0 => segments.push(Segment {
+                    region: MemRegion {
+                        start_addr: p_vaddr,
+                        end_addr: p_end,
+                        size: p_memsz as usize,
+                        readable: p_flags & PF_R != 0,
+                        writeable: p_flags & PF_W != 0,
+                        execable: p_flags & PF_X != 0,
+                        private: true,
+                        shared: false,
+                        name: format!("{:X}", p_vaddr),
+                        file_offset: 0,
+                        pathname: String::new(),
+                    },
+                    file_offset: p_offset,
+                    file_size: p_filesz.min(p_memsz),
+                }),
+                PT_NOTE => {
+                    check_in_file("note segment", p_offset, p_filesz, file_len)?;
+                    let mut notes = vec![0u8; p_filesz as usize];
+                    file.read_exact_at(&mut notes, p_offset)?;
+                    mapped_files.extend(CoreFileBackend::parse_nt_file(&notes)?);
+                }
+                _ => {}
+            }
+        }
+        // Name each segment after the file mapped over it, just like /proc/<pid>/maps would
+        for seg in segments.iter_mut() {
+            if let Some((start, _, file_offset, name)) =
+                mapped_files.iter().find(|(start, end, _, _)| {
+                    *start <= seg.region.start_addr && *end > seg.region.start_addr
+                })
+            {
+                seg.region.name = name.clone();
+                seg.region.pathname = name.clone();
+                seg.region.file_offset = file_offset + (seg.region.start_addr - start);
+            }
+        }
+        segments.sort_by_key(|s| s.region.start_addr);
+        Ok(CoreFileBackend { file, segments })
+    }
+
+    /// Extract (start, end, file offset, path) of each mapping from the NT_FILE note
+    fn parse_nt_file(notes: &[u8]) -> Result<Vec<(u64, u64, u64, String)>> {
+        let mut files = Vec::new();
+        let mut off = 0;
+        while off + 12 <= notes.len() {
+            let namesz = u32_at(notes, off)? as usize;
+            let descsz = u32_at(notes, off + 4)? as usize;
+            let ntype = u32_at(notes, off + 8)?;
+            let name_off = off + 12;
+            let desc_off = name_off + align4(namesz);
+            off = desc_off + align4(descsz);
+            if ntype != NT_FILE || notes.get(name_off..name_off + 4) != Some(b"CORE") {
+                continue;
+            }
+            let desc = notes.get(desc_off..desc_off + descsz).ok_or_else(|| {
+                NoviMemError::Parse("NT_FILE note runs past its segment".to_string())
+            })?;
+            let count = u64_at(desc, 0)?;
+            let page_size = u64_at(desc, 8)?;
+            // Entries follow the count and page size, file names follow the entries
+            if count > (descsz.saturating_sub(16) / 24) as u64 {
+                return Err(NoviMemError::Parse(format!(
+                    "NT_FILE note claims {} entries, more than it holds",
+                    count
+                )));
+            }
+            let count = count as usize;
+            let names_off = count
+                .checked_mul(24)
+                .and_then(|n| n.checked_add(16))
+                .ok_or_else(|| NoviMemError::Parse("NT_FILE entry count overflows".to_string()))?;
+            let mut names = desc
+                .get(names_off..)
+                .unwrap_or_default()
+                .split(|b| *b == 0)
+                .map(|n| String::from_utf8_lossy(n).to_string());
+            for i in 0..count {
+                let entry = 16 + i * 24;
+                let start = u64_at(desc, entry)?;
+                let end = u64_at(desc, entry + 8)?;
+                let file_offset = u64_at(desc, entry + 16)?
+                    .checked_mul(page_size)
+                    .ok_or_else(|| {
+                        NoviMemError::Parse("NT_FILE file offset overflows".to_string())
+                    })?;
+                files.push((start, end, file_offset, names.next().unwrap_or_default()));
+            }
+        }
+        Ok(files)
+    }
+}
+
+impl MemoryBackend for CoreFileBackend {
+    fn read(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        let seg = self
+            .segments
+            .iter()
+            .find(|s| s.region.start_addr <= addr && s.region.end_addr > addr)
+            .ok_or(NoviMemError::UnmappedAddress(addr))?;
+        let offset = addr - seg.region.start_addr;
+        let len = buf.len().min((seg.region.end_addr - addr) as usize);
+        if offset < seg.file_size {
+            let in_file = len.min((seg.file_size - offset) as usize);
+            Ok(self
+                .file
+                .read_at(&mut buf[..in_file], seg.file_offset + offset)?)
+        } else {
+            // Pages the kernel did not write to the core (filesz < memsz) read back as zero
+            buf[..len].iter_mut().for_each(|b| *b = 0);
+            Ok(len)
+        }
+    }
+
+    fn write(&self, _addr: u64, _buf: &[u8]) -> Result<usize> {
+        Err(NoviMemError::ReadOnly)
+    }
+
+    fn regions(&self) -> Result<Vec<MemRegion>> {
+        Ok(self.segments.iter().map(|s| s.region.clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::novimem::NoviMem;
+    use std::{fs, process};
+
+    fn phdr(p_type: u32, flags: u32, offset: u64, vaddr: u64, filesz: u64, memsz: u64) -> Vec<u8> {
+        let mut ph = Vec::new();
+        ph.extend(&p_type.to_le_bytes());
+        ph.extend(&flags.to_le_bytes());
+        ph.extend(&offset.to_le_bytes());
+        ph.extend(&vaddr.to_le_bytes());
+        ph.extend(&0u64.to_le_bytes());
+        ph.extend(&filesz.to_le_bytes());
+        ph.extend(&memsz.to_le_bytes());
+        ph.extend(&0x1000u64.to_le_bytes());
+        ph
+    }
+
+    /// A core with one file-backed segment, one partially dumped anonymous segment and an NT_FILE note
+    fn build_core() -> Vec<u8> {
+        let mut desc = Vec::new();
+        desc.extend(&1u64.to_le_bytes());
+        desc.extend(&0x1000u64.to_le_bytes());
+        desc.extend(&0x40_0000u64.to_le_bytes());
+        desc.extend(&0x40_1000u64.to_le_bytes());
+        desc.extend(&2u64.to_le_bytes());
+        desc.extend(b"/usr/bin/game\0");
+        let mut note = Vec::new();
+        note.extend(&5u32.to_le_bytes());
+        note.extend(&(desc.len() as u32).to_le_bytes());
+        note.extend(&NT_FILE.to_le_bytes());
+        note.extend(b"CORE\0\0\0\0");
+        note.extend(&desc);
+        while note.len() % 4 != 0 {
+            note.push(0);
+        }
+
+        let data_off = 64 + 3 * 56;
+        let note_off = data_off as u64;
+        let load1_off = note_off + note.len() as u64;
+        let load2_off = load1_off + 0x1000;
+        let mut core = vec![0u8; 64];
+        core[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        core[0x10..0x12].copy_from_slice(&ET_CORE.to_le_bytes());
+        core[0x20..0x28].copy_from_slice(&64u64.to_le_bytes());
+        core[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+        core[0x38..0x3A].copy_from_slice(&3u16.to_le_bytes());
+        core.extend(phdr(PT_NOTE, 0, note_off, 0, note.len() as u64, 0));
+        core.extend(phdr(
+            PT_LOAD,
+            PF_R | PF_W,
+            load1_off,
+            0x40_0000,
+            0x1000,
+            0x1000,
+        ));
+        core.extend(phdr(
+            PT_LOAD,
+            PF_R | PF_W,
+            load2_off,
+            0x7000_0000,
+            0x1000,
+            0x2000,
+        ));
+        core.extend(&note);
+        let mut load1 = vec![0u8; 0x1000];
+        load1[0x20..0x24].copy_from_slice(&4321u32.to_le_bytes());
+        core.extend(&load1);
+        let mut load2 = vec![0xAAu8; 0x1000];
+        load2[0x100..0x104].copy_from_slice(&4321u32.to_le_bytes());
+        core.extend(&load2);
+        core
+    }
+
+    #[test]
+    fn test_core_file() {
+        let path = std::env::temp_dir().join(format!("novimem-core-{}", process::id()));
+        fs::write(&path, build_core()).unwrap();
+        let backend = CoreFileBackend::open(&path).unwrap();
+        let regions = backend.regions().unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].name, "/usr/bin/game");
+        assert_eq!(regions[0].pathname, "/usr/bin/game");
+        assert_eq!(regions[0].file_offset, 0x2000);
+        assert_eq!(regions[1].pathname, "");
+        assert_eq!(regions[1].name, "70000000");
+        assert_eq!(regions[1].size, 0x2000);
+
+        // Reads spanning the dumped and the missing part of a segment
+        let mut buf = [0u8; 4];
+        assert_eq!(backend.read(0x7000_0FFE, &mut buf).unwrap(), 2);
+        assert_eq!(backend.read(0x7000_1000, &mut buf).unwrap(), 4);
+        assert_eq!(buf, [0u8; 4]);
+        assert!(backend.read(0x1234, &mut buf).is_err());
+
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("game")).unwrap();
+        assert_eq!(m.search(&4321u32.to_le_bytes()).unwrap(), 2);
+        assert_eq!(m.results(), &vec![0x40_0020, 0x7000_0100]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_core() {
+        let path = std::env::temp_dir().join(format!("novimem-bad-core-{}", process::id()));
+        let parse_error = |core: &[u8]| {
+            fs::write(&path, core).unwrap();
+            matches!(CoreFileBackend::open(&path), Err(NoviMemError::Parse(_)))
+        };
+        let note_off = 64 + 3 * 56;
+        let desc_off = note_off + 20;
+
+        // A note segment larger than the file is refused before anything is allocated
+        let mut core = build_core();
+        core[64 + 32..64 + 40].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(parse_error(&core));
+
+        // So are entry counts the note cannot hold and offsets that overflow
+        let mut core = build_core();
+        core[desc_off..desc_off + 8].copy_from_slice(&(u64::MAX / 8).to_le_bytes());
+        assert!(parse_error(&core));
+        let mut core = build_core();
+        core[desc_off + 8..desc_off + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(parse_error(&core));
+
+        // And segments that wrap around the address space
+        let mut core = build_core();
+        core[64 + 2 * 56 + 40..64 + 2 * 56 + 48].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(parse_error(&core));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+use super::MemoryBackend;
+use crate::novimem::{
+    error::{NoviMemError, Result},
+    MemRegion,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// Describes where each region of a capture lives on disk
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DumpManifest {
+    pub pname: String,
+    pub regions: Vec<DumpEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpEntry {
+    pub region: MemRegion,
+    /// File holding the region's bytes, relative to the manifest
+    pub file: String,
+    /// Offset of the region's first byte within `file`
+    #[serde(default)]
+    pub file_offset: u64,
+}
+
+struct DumpRegion {
+    region: MemRegion,
+    file: File,
+    file_offset: u64,
+}
+
+/// Serves a capture from a directory of region dumps.
+/// The directory either holds a `manifest.json` (possibly pointing into one whole-process dump)
+/// or bare `START.SIZE.END.dump` files as written by `MemRegion::dump_to_file`.
+pub struct DumpDirBackend {
+    pname: String,
+    regions: Vec<DumpRegion>,
+}
+
+impl DumpDirBackend {
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<DumpDirBackend> {
+        let dir = dir.as_ref();
+        let manifest_path = dir.join(MANIFEST_NAME);
+        let manifest = if manifest_path.exists() {
+            serde_json::from_slice(&fs::read(&manifest_path)?)?
+        } else {
+            DumpDirBackend::scan_dir(dir)?
+        };
+        let pname = if manifest.pname.is_empty() {
+            dir.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default()
+        } else {
+            manifest.pname
+        };
+        let mut regions = Vec::with_capacity(manifest.regions.len());
+        for entry in manifest.regions {
+            regions.push(DumpRegion {
+                file: File::open(dir.join(&entry.file))?,
+                region: entry.region,
+                file_offset: entry.file_offset,
+            });
+        }
+        regions.sort_by_key(|r| r.region.start_addr);
+        Ok(DumpDirBackend { pname, regions })
+    }
+
+    /// Name of the process the capture was taken from
+    pub fn pname(&self) -> &str {
+        &self.pname
+    }
+
+    /// Build a manifest from `START.SIZE.END.dump` file names
+    fn scan_dir(dir: &Path) -> Result<DumpManifest> {
+        let mut manifest = DumpManifest::default();
+        for entry in fs::read_dir(dir)? {
+            let path: PathBuf = entry?.path();
+            let fname = match path.file_name().and_then(|n| n.to_str()) {
+                Some(n) if n.ends_with(".dump") => n.to_string(),
+                _ => continue,
+            };
+            let parts: Vec<&str> = fname.trim_end_matches(".dump").split('.').collect();
+            if parts.len() != 3 {
+                return Err(NoviMemError::Parse(format!("dump file name '{}'", fname)));
+            }
+            let parse = |s: &str| {
+                u64::from_str_radix(s, 16)
+                    .map_err(|e| NoviMemError::Parse(format!("dump file name '{}': {}", fname, e)))
+            };
+            let start = parse(parts[0])?;
+            let size = parse(parts[1])?;
+            let end = parse(parts[2])?;
+            if start.checked_add(size) != Some(end) {
+                return Err(NoviMemError::Parse(format!(
+                    "dump file name '{}': size does not match bounds",
+                    fname
+                )));
+            }
+            manifest.regions.push(DumpEntry {
+                region: MemRegion {
+                    start_addr: start,
+                    end_addr: end,
+                    size: size as usize,
+                    readable: true,
+                    writeable: true,
+                    execable: false,
+                    private: true,
+                    shared: false,
+                    name: format!("{:X}", start),
+                    file_offset: 0,
+                    pathname: String::new(),
+                },
+                file: fname,
+                file_offset: 0,
+            });
+        }
+        Ok(manifest)
+    }
+}
+
+impl MemoryBackend for DumpDirBackend {
+    fn read(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        let r = self
+            .regions
+            .iter()
+            .find(|r| r.region.start_addr <= addr && r.region.end_addr > addr)
+            .ok_or(NoviMemError::UnmappedAddress(addr))?;
+        let offset = addr - r.region.start_addr;
+        let len = buf.len().min((r.region.end_addr - addr) as usize);
+        Ok(r.file.read_at(&mut buf[..len], r.file_offset + offset)?)
+    }
+
+    fn write(&self, _addr: u64, _buf: &[u8]) -> Result<usize> {
+        Err(NoviMemError::ReadOnly)
+    }
+
+    fn regions(&self) -> Result<Vec<MemRegion>> {
+        Ok(self.regions.iter().map(|r| r.region.clone()).collect())
+    }
+}
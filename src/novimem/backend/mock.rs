@@ -0,0 +1,144 @@
+use super::MemoryBackend;
+use crate::novimem::{
+    error::{NoviMemError, Result},
+    region_reader::PAGE_SIZE,
+    MemRegion,
+};
+use std::sync::RwLock;
+
+struct MockRegion {
+    region: MemRegion,
+    data: Vec<u8>,
+}
+
+/// A fabricated address space held entirely in memory
+#[derive(Default)]
+pub struct MockBackend {
+    regions: RwLock<Vec<MockRegion>>,
+    /// Start addresses of mapped pages that fail to read, like guard pages
+    unreadable: RwLock<Vec<u64>>,
+}
+
+impl MockBackend {
+    pub fn new() -> MockBackend {
+        MockBackend::default()
+    }
+
+    /// Map `data` at `start_addr` as a private read/write region called `name`
+    pub fn add_region(&mut self, name: &str, start_addr: u64, data: Vec<u8>) -> &mut MemRegion {
+        let regions = self.regions.get_mut().unwrap();
+        let idx = MockBackend::insert(regions, name, start_addr, data);
+        &mut regions[idx].region
+    }
+
+    /// Like `add_region`, for a backend that is already in use
+    pub fn map_region(&self, name: &str, start_addr: u64, data: Vec<u8>) {
+        MockBackend::insert(&mut self.regions.write().unwrap(), name, start_addr, data);
+    }
+
+    /// Remove the region starting at `start_addr`
+    pub fn unmap_region(&self, start_addr: u64) -> bool {
+        let mut regions = self.regions.write().unwrap();
+        let len = regions.len();
+        regions.retain(|r| r.region.start_addr != start_addr);
+        regions.len() != len
+    }
+
+    /// Make the page at `page_addr` fail to read while staying mapped
+    pub fn set_unreadable(&self, page_addr: u64) {
+        self.unreadable
+            .write()
+            .unwrap()
+            .push(page_addr & !(PAGE_SIZE - 1));
+    }
+
+    fn insert(regions: &mut Vec<MockRegion>, name: &str, start_addr: u64, data: Vec<u8>) -> usize {
+        let region = MemRegion {
+            start_addr,
+            end_addr: start_addr + data.len() as u64,
+            size: data.len(),
+            readable: true,
+            writeable: true,
+            execable: false,
+            private: true,
+            shared: false,
+            name: name.to_string(),
+            file_offset: 0,
+            pathname: name.to_string(),
+        };
+        let idx = regions
+            .iter()
+            .position(|r| r.region.start_addr > start_addr)
+            .unwrap_or(regions.len());
+        regions.insert(idx, MockRegion { region, data });
+        idx
+    }
+}
+
+impl MemoryBackend for MockBackend {
+    fn read(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        let regions = self.regions.read().unwrap();
+        let unreadable = self.unreadable.read().unwrap();
+        let mut read = 0;
+        // Keep copying while the requested range runs into adjacent regions
+        while read < buf.len() {
+            let cur = addr + read as u64;
+            let page = cur & !(PAGE_SIZE - 1);
+            // Stop short of the next page that fails, as a real read would
+            let readable_to = unreadable
+                .iter()
+                .filter(|p| **p >= page)
+                .min()
+                .map_or(u64::MAX, |p| *p);
+            match regions
+                .iter()
+                .find(|r| r.region.start_addr <= cur && r.region.end_addr > cur)
+                .filter(|_| readable_to > cur)
+            {
+                Some(r) => {
+                    let offset = (cur - r.region.start_addr) as usize;
+                    let n = (buf.len() - read)
+                        .min(r.data.len() - offset)
+                        .min((readable_to - cur) as usize);
+                    buf[read..read + n].copy_from_slice(&r.data[offset..offset + n]);
+                    read += n;
+                }
+                None if read == 0 => return Err(NoviMemError::UnmappedAddress(addr)),
+                None => break,
+            }
+        }
+        Ok(read)
+    }
+
+    fn write(&self, addr: u64, buf: &[u8]) -> Result<usize> {
+        let mut regions = self.regions.write().unwrap();
+        let mut written = 0;
+        while written < buf.len() {
+            let cur = addr + written as u64;
+            match regions
+                .iter_mut()
+                .find(|r| r.region.start_addr <= cur && r.region.end_addr > cur)
+            {
+                Some(r) => {
+                    let offset = (cur - r.region.start_addr) as usize;
+                    let n = (buf.len() - written).min(r.data.len() - offset);
+                    r.data[offset..offset + n].copy_from_slice(&buf[written..written + n]);
+                    written += n;
+                }
+                None if written == 0 => return Err(NoviMemError::UnmappedAddress(addr)),
+                None => break,
+            }
+        }
+        Ok(written)
+    }
+
+    fn regions(&self) -> Result<Vec<MemRegion>> {
+        Ok(self
+            .regions
+            .read()
+            .unwrap()
+            .iter()
+            .map(|r| r.region.clone())
+            .collect())
+    }
+}
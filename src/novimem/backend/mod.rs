@@ -0,0 +1,112 @@
+pub mod core_file;
+pub mod dump_dir;
+pub mod mock;
+pub mod proc_mem;
+pub mod process_vm;
+
+use super::{
+    error::{NoviMemError, Result},
+    MemRegion,
+};
+use std::{
+    fs::OpenOptions,
+    io::{prelude::*, BufReader},
+    sync::Arc,
+};
+
+/// A source of process memory that `NoviMem` can scan.
+/// Backends are shared with background threads, such as the one keeping frozen values,
+/// and scans read them from every thread of the rayon pool at once.
+pub trait MemoryBackend: Send + Sync {
+    /// Read up to `buf.len()` bytes starting at `addr`, returning how many were read.
+    /// A read that starts at an address that is not backed by anything is an error.
+    fn read(&self, addr: u64, buf: &mut [u8]) -> Result<usize>;
+
+    /// Write `buf` at `addr`, returning how many bytes were written
+    fn write(&self, addr: u64, buf: &[u8]) -> Result<usize>;
+
+    /// Enumerate every region of the address space, in ascending address order
+    fn regions(&self) -> Result<Vec<MemRegion>>;
+
+    /// The pid of the live process behind this backend, if there is one
+    fn pid(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// A backend can be shared, e.g. to change a `MockBackend` while a `NoviMem` scans it
+impl<T: MemoryBackend + ?Sized> MemoryBackend for Arc<T> {
+    fn read(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(addr, buf)
+    }
+
+    fn write(&self, addr: u64, buf: &[u8]) -> Result<usize> {
+        (**self).write(addr, buf)
+    }
+
+    fn regions(&self) -> Result<Vec<MemRegion>> {
+        (**self).regions()
+    }
+
+    fn pid(&self) -> Option<u32> {
+        (**self).pid()
+    }
+}
+
+/// Parse `/proc/<pid>/maps` into regions
+pub fn parse_maps(pid: u32) -> Result<Vec<MemRegion>> {
+    use regex::RegexBuilder;
+    let mapsfile = OpenOptions::new()
+        .read(true)
+        .write(false)
+        .create(false)
+        .open(format!("/proc/{}/maps", pid))
+        .map_err(|e| NoviMemError::from_mem_io(pid, 0, e))?;
+    let regex_str =
+        //address 1,2                    perms 3,4,5,6            offset 7         dev                           inode     pathname 8
+        r"([0-9A-Fa-f]+)-([0-9A-Fa-f]+) ([-r])([-w])([-x])([-ps]) ([0-9A-Fa-f]+) (?:[0-9A-Fa-f]+:[0-9A-Fa-f]+) (?:\d+)\s*(.*)?";
+    let mut builder = RegexBuilder::new(regex_str);
+    builder
+        .unicode(true)
+        .dot_matches_new_line(true)
+        .case_insensitive(false);
+    let re = builder
+        .build()
+        .map_err(|e| NoviMemError::Parse(e.to_string()))?;
+    let mut regions = Vec::new();
+    // Parse the maps file to find regions of interest
+    for line in BufReader::new(mapsfile).lines() {
+        let resline = line?;
+        let cap = re
+            .captures(&resline)
+            .ok_or_else(|| NoviMemError::Parse(format!("maps line '{}'", resline)))?;
+        let parse_addr = |s: &str| {
+            u64::from_str_radix(s, 16)
+                .map_err(|e| NoviMemError::Parse(format!("address '{}': {}", s, e)))
+        };
+        let start = parse_addr(&cap[1])?;
+        let end = parse_addr(&cap[2])?;
+        let pathname = cap
+            .get(8)
+            .map(|n| n.as_str().replace('\0', ""))
+            .unwrap_or_default();
+        regions.push(MemRegion {
+            start_addr: start,
+            end_addr: end,
+            size: (end - start) as usize,
+            readable: &cap[3] == "r",
+            writeable: &cap[4] == "w",
+            execable: &cap[5] == "x",
+            private: &cap[6] == "p",
+            shared: &cap[6] == "s",
+            name: if pathname.is_empty() {
+                format!("{:X}", start)
+            } else {
+                pathname.clone()
+            },
+            file_offset: parse_addr(&cap[7])?,
+            pathname,
+        });
+    }
+    Ok(regions)
+}
@@ -0,0 +1,55 @@
+use super::{parse_maps, MemoryBackend};
+use crate::novimem::{
+    error::{NoviMemError, Result},
+    MemRegion,
+};
+use std::{
+    fs::{File, OpenOptions},
+    os::unix::fs::FileExt,
+};
+
+/// Reads and writes a live process through `/proc/<pid>/mem`
+///
+/// Every scan worker reads through the one handle. Reads are positioned (`pread`), so
+/// there is no shared file offset for the workers to take turns on, and the kernel only
+/// takes the target's mm lock for reading while it copies. A handle per worker would buy
+/// nothing but more open files; if the fd itself ever needs to go, `ProcessVmBackend`
+/// reads the same memory with `process_vm_readv` and no fd at all.
+pub struct ProcMemBackend {
+    pid: u32,
+    memfile: File,
+}
+
+impl ProcMemBackend {
+    pub fn new(pid: u32) -> Result<ProcMemBackend> {
+        let memfile = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(false)
+            .open(format!("/proc/{}/mem", pid))
+            .map_err(|e| NoviMemError::from_mem_io(pid, 0, e))?;
+        Ok(ProcMemBackend { pid, memfile })
+    }
+}
+
+impl MemoryBackend for ProcMemBackend {
+    fn read(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        self.memfile
+            .read_at(buf, addr)
+            .map_err(|e| NoviMemError::from_mem_io(self.pid, addr, e))
+    }
+
+    fn write(&self, addr: u64, buf: &[u8]) -> Result<usize> {
+        self.memfile
+            .write_at(buf, addr)
+            .map_err(|e| NoviMemError::from_mem_io(self.pid, addr, e))
+    }
+
+    fn regions(&self) -> Result<Vec<MemRegion>> {
+        parse_maps(self.pid)
+    }
+
+    fn pid(&self) -> Option<u32> {
+        Some(self.pid)
+    }
+}
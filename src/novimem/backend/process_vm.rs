@@ -0,0 +1,82 @@
+use super::{parse_maps, MemoryBackend};
+use crate::novimem::{
+    error::{NoviMemError, Result},
+    MemRegion,
+};
+use std::io;
+
+/// Reads and writes a live process with `process_vm_readv`/`process_vm_writev`.
+/// Unlike `/proc/<pid>/mem` this honours page protections, so read-only pages cannot be written.
+pub struct ProcessVmBackend {
+    pid: u32,
+}
+
+impl ProcessVmBackend {
+    pub fn new(pid: u32) -> Result<ProcessVmBackend> {
+        // Make sure the process exists before handing out a backend. This only proves we may
+        // signal it: whether we may read it (ptrace access) shows on the first read, since a
+        // zero-length process_vm_readv returns before any permission check.
+        if unsafe { libc::kill(pid as libc::pid_t, 0) } != 0 {
+            return Err(NoviMemError::from_mem_io(
+                pid,
+                0,
+                io::Error::last_os_error(),
+            ));
+        }
+        Ok(ProcessVmBackend { pid })
+    }
+}
+
+impl MemoryBackend for ProcessVmBackend {
+    fn read(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        let local = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let remote = libc::iovec {
+            iov_base: addr as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let n =
+            unsafe { libc::process_vm_readv(self.pid as libc::pid_t, &local, 1, &remote, 1, 0) };
+        if n < 0 {
+            Err(NoviMemError::from_mem_io(
+                self.pid,
+                addr,
+                io::Error::last_os_error(),
+            ))
+        } else {
+            Ok(n as usize)
+        }
+    }
+
+    fn write(&self, addr: u64, buf: &[u8]) -> Result<usize> {
+        let local = libc::iovec {
+            iov_base: buf.as_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let remote = libc::iovec {
+            iov_base: addr as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let n =
+            unsafe { libc::process_vm_writev(self.pid as libc::pid_t, &local, 1, &remote, 1, 0) };
+        if n < 0 {
+            Err(NoviMemError::from_mem_io(
+                self.pid,
+                addr,
+                io::Error::last_os_error(),
+            ))
+        } else {
+            Ok(n as usize)
+        }
+    }
+
+    fn regions(&self) -> Result<Vec<MemRegion>> {
+        parse_maps(self.pid)
+    }
+
+    fn pid(&self) -> Option<u32> {
+        Some(self.pid)
+    }
+}
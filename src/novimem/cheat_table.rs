@@ -0,0 +1,313 @@
+use super::{
+    error::{NoviMemError, Result},
+    pointer_scan::{self, PointerChain},
+    value::{DataType, Value},
+    NoviMem,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path};
+
+/// Where a table entry lives, in a form that survives restarts where possible
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AddressExpr {
+    Absolute(u64),
+    /// An offset from where a module is loaded
+    Module {
+        module: String,
+        offset: u64,
+    },
+    Pointer(PointerChain),
+}
+
+impl AddressExpr {
+    /// Parse `7FFE1000`, `game+1A0` or a pointer chain `game+10 -> +8 -> +4`,
+    /// all in hex; the spaces around `->` are optional
+    pub fn parse(s: &str) -> Result<AddressExpr> {
+        let err = || NoviMemError::Parse(format!("'{}' is not a valid address expression", s));
+        let hex = |h: &str| u64::from_str_radix(h.trim().trim_start_matches("0x"), 16);
+        let mut parts = s.split("->");
+        let base = parts.next().unwrap_or_default().trim();
+        let offsets = parts
+            .map(|o| hex(o.trim().trim_start_matches('+')))
+            .collect::<std::result::Result<Vec<u64>, _>>()
+            .map_err(|_| err())?;
+        let expr = match base.rfind('+') {
+            Some(idx) if idx > 0 => AddressExpr::Module {
+                module: base[..idx].to_string(),
+                offset: hex(&base[idx + 1..]).map_err(|_| err())?,
+            },
+            _ => AddressExpr::Absolute(hex(base).map_err(|_| err())?),
+        };
+        match (expr, offsets.is_empty()) {
+            (expr, true) => Ok(expr),
+            (AddressExpr::Module { module, offset }, false) => {
+                Ok(AddressExpr::Pointer(PointerChain {
+                    module,
+                    offset,
+                    offsets,
+                }))
+            }
+            _ => Err(err()),
+        }
+    }
+}
+
+impl fmt::Display for AddressExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressExpr::Absolute(addr) => write!(f, "{:X}", addr),
+            AddressExpr::Module { module, offset } => write!(
+                f,
+                "{}+{:X}",
+                module.rsplit('/').next().unwrap_or(module),
+                offset
+            ),
+            AddressExpr::Pointer(chain) => write!(f, "{}", chain),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheatEntry {
+    pub description: String,
+    pub dtype: DataType,
+    pub address: AddressExpr,
+    /// Kept at this value while the table is loaded
+    #[serde(default)]
+    pub frozen: Option<Value>,
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+/// Named, typed addresses saved as JSON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CheatTable {
+    pub entries: Vec<CheatEntry>,
+}
+
+impl CheatTable {
+    pub fn load(path: &Path) -> Result<CheatTable> {
+        let json = fs::read(path)?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl NoviMem {
+    pub fn resolve_address(&self, expr: &AddressExpr) -> Result<u64> {
+        match expr {
+            AddressExpr::Absolute(addr) => Ok(*addr),
+            AddressExpr::Module { module, offset } => Ok(self.module_base(module)? + offset),
+            AddressExpr::Pointer(chain) => self.resolve_chain(chain),
+        }
+    }
+
+    /// Express `addr` relative to the module it is in, if any
+    pub fn address_expr(&self, addr: u64) -> AddressExpr {
+        pointer_scan::modules(&self.regions)
+            .into_iter()
+            .find(|m| m.start_addr <= addr && m.end_addr > addr)
+            .map(|m| AddressExpr::Module {
+                offset: addr - m.start_addr,
+                module: m.name,
+            })
+            .unwrap_or(AddressExpr::Absolute(addr))
+    }
+
+    pub fn cheat_table(&self) -> &CheatTable {
+        &self.cheat_table
+    }
+
+    pub fn add_cheat(&mut self, entry: CheatEntry) -> Result<()> {
+        if let Some(value) = entry.frozen {
+            let addr = self.resolve_address(&entry.address)?;
+            self.freeze(addr, entry.dtype, value)?;
+        }
+        self.cheat_table.entries.push(entry);
+        Ok(())
+    }
+
+    pub fn remove_cheat(&mut self, idx: usize) -> Option<CheatEntry> {
+        if idx >= self.cheat_table.entries.len() {
+            return None;
+        }
+        let entry = self.cheat_table.entries.remove(idx);
+        if entry.frozen.is_some() {
+            if let Ok(addr) = self.resolve_address(&entry.address) {
+                self.unfreeze(addr);
+            }
+        }
+        Some(entry)
+    }
+
+    /// Freeze the entry at `value`, or stop freezing it when `None`
+    pub fn set_cheat_frozen(&mut self, idx: usize, value: Option<Value>) -> Result<()> {
+        let entry = self
+            .cheat_table
+            .entries
+            .get(idx)
+            .ok_or_else(|| NoviMemError::Parse(format!("no table entry {}", idx)))?;
+        let (dtype, addr) = (entry.dtype, self.resolve_address(&entry.address)?);
+        match value {
+            Some(v) => self.freeze(addr, dtype, v)?,
+            None => {
+                self.unfreeze(addr);
+            }
+        }
+        self.cheat_table.entries[idx].frozen = value;
+        Ok(())
+    }
+
+    pub fn set_cheat_group(&mut self, idx: usize, group: Option<String>) -> bool {
+        match self.cheat_table.entries.get_mut(idx) {
+            Some(entry) => {
+                entry.group = group;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The current address and value of every entry
+    pub fn cheat_values(&self) -> Vec<Result<(u64, Value)>> {
+        self.cheat_table
+            .entries
+            .iter()
+            .map(|e| {
+                let addr = self.resolve_address(&e.address)?;
+                Ok((addr, e.dtype.decode(&self.getval(addr, e.dtype.size())?)))
+            })
+            .collect()
+    }
+
+    /// Replace the table, freezing the entries that have a frozen value.
+    /// Returns how many of them could not be resolved right now.
+    pub fn load_cheat_table(&mut self, path: &Path) -> Result<usize> {
+        let table = CheatTable::load(path)?;
+        for idx in (0..self.cheat_table.entries.len()).rev() {
+            self.remove_cheat(idx);
+        }
+        let mut failed = 0;
+        for entry in table.entries {
+            if self.add_cheat(entry.clone()).is_err() {
+                // Keep the entry even if it does not resolve in this process
+                failed += 1;
+                self.cheat_table.entries.push(entry);
+            }
+        }
+        Ok(failed)
+    }
+
+    pub fn save_cheat_table(&self, path: &Path) -> Result<()> {
+        self.cheat_table.save(path)
+    }
+
+    pub fn print_cheat_table(&self) {
+        let values = self.cheat_values();
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by_key(|i| &self.cheat_table.entries[*i].group);
+        let mut group = None;
+        for idx in order {
+            let entry = &self.cheat_table.entries[idx];
+            if entry.group.is_some() && entry.group != group {
+                group = entry.group.clone();
+                println!("[{}]", group.as_deref().unwrap_or_default());
+            }
+            let current = match &values[idx] {
+                Ok((addr, value)) => format!("{:X} = {}", addr, value),
+                Err(e) => format!("({})", e),
+            };
+            let frozen = entry
+                .frozen
+                .map(|v| format!(" frozen at {}", v))
+                .unwrap_or_default();
+            println!(
+                "{}:\t{}\t{} {}\t{}{}",
+                idx, entry.description, entry.dtype, entry.address, current, frozen
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::novimem::backend::mock::MockBackend;
+    use std::process;
+
+    #[test]
+    fn test_address_expr() {
+        assert_eq!(
+            AddressExpr::parse("0x7FFE1000").unwrap(),
+            AddressExpr::Absolute(0x7FFE_1000)
+        );
+        let chain = AddressExpr::parse("game+10->+8 -> 4").unwrap();
+        assert_eq!(
+            chain,
+            AddressExpr::Pointer(PointerChain {
+                module: "game".to_string(),
+                offset: 0x10,
+                offsets: vec![8, 4],
+            })
+        );
+        assert_eq!(chain.to_string(), "game+10 -> +8 -> +4");
+        assert_eq!(
+            AddressExpr::parse(&AddressExpr::parse("libc.so.6+1A0").unwrap().to_string()).unwrap(),
+            AddressExpr::Module {
+                module: "libc.so.6".to_string(),
+                offset: 0x1A0
+            }
+        );
+        assert!(AddressExpr::parse("1000 -> +8").is_err());
+        assert!(AddressExpr::parse("game+zz").is_err());
+    }
+
+    #[test]
+    fn test_cheat_table() {
+        let mut backend = MockBackend::new();
+        let mut data = vec![0u8; 0x40];
+        data[0x10..0x18].copy_from_slice(&0x1000u64.to_le_bytes());
+        backend.add_region("/usr/bin/game", 0x40_0000, data);
+        backend.add_region("[heap]", 0x1000, vec![0u8; 0x100]);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+
+        let ammo = m.address_expr(0x40_0020);
+        assert_eq!(ammo.to_string(), "game+20");
+        m.add_cheat(CheatEntry {
+            description: "ammo".to_string(),
+            dtype: DataType::U32,
+            address: ammo,
+            frozen: Some(Value::Int(999)),
+            group: Some("player".to_string()),
+        })
+        .unwrap();
+        m.add_cheat(CheatEntry {
+            description: "health".to_string(),
+            dtype: DataType::F32,
+            address: AddressExpr::parse("game+10 -> +8").unwrap(),
+            frozen: None,
+            group: None,
+        })
+        .unwrap();
+        m.setval(0x1008, &75.5f32.to_le_bytes()).unwrap();
+        let values: Vec<(u64, Value)> = m.cheat_values().into_iter().map(|v| v.unwrap()).collect();
+        assert_eq!(
+            values,
+            vec![(0x40_0020, Value::Int(999)), (0x1008, Value::Float(75.5))]
+        );
+
+        let path = std::env::temp_dir().join(format!("novimem-table-{}.json", process::id()));
+        m.save_cheat_table(&path).unwrap();
+        let saved = m.cheat_table().clone();
+        assert!(m.remove_cheat(0).is_some());
+        assert!(m.frozen().is_empty());
+        assert_eq!(m.load_cheat_table(&path).unwrap(), 0);
+        assert_eq!(m.cheat_table(), &saved);
+        assert_eq!(m.frozen().len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
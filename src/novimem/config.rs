@@ -0,0 +1,34 @@
+use super::{error::Result, region_filter::RegionFilter, snapshot::SnapshotOptions, NoviMem};
+use serde::{Deserialize, Serialize};
+use std::{fs, io::ErrorKind, path::Path};
+
+/// Settings read from the working directory at startup
+pub const CONFIG_FILE: &str = "novimem.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub region_filter: RegionFilter,
+    pub snapshots: SnapshotOptions,
+}
+
+impl Config {
+    /// Read the config at `path`, or the defaults if there is none
+    pub fn load(path: &Path) -> Result<Config> {
+        match fs::read(path) {
+            Ok(json) => Ok(serde_json::from_slice(&json)?),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn apply(&self, mem: &mut NoviMem) -> Result<()> {
+        mem.set_snapshot_options(self.snapshots.clone());
+        mem.set_region_filter(self.region_filter.clone())
+    }
+}
@@ -0,0 +1,96 @@
+use std::{error, fmt, io};
+
+#[derive(Debug)]
+pub enum NoviMemError {
+    /// We are not allowed to open or access the target's memory
+    PermissionDenied {
+        pid: u32,
+    },
+    /// The target process no longer exists
+    ProcessGone {
+        pid: u32,
+    },
+    /// Fewer bytes than requested could be read
+    ShortRead {
+        addr: u64,
+        expected: usize,
+        read: usize,
+    },
+    /// The address is not backed by any mapping in the target
+    UnmappedAddress(u64),
+    /// Input (maps lines, search patterns, saved files, ...) could not be parsed
+    Parse(String),
+    /// A comparison search was requested before any snapshot was taken
+    NoSnapshot,
+    /// The memory source cannot be written to (e.g. an offline capture)
+    ReadOnly,
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, NoviMemError>;
+
+impl NoviMemError {
+    /// Classify an I/O error raised while touching the memory of `pid` at `addr`
+    pub fn from_mem_io(pid: u32, addr: u64, e: io::Error) -> NoviMemError {
+        match e.raw_os_error() {
+            Some(libc::EACCES) | Some(libc::EPERM) => NoviMemError::PermissionDenied { pid },
+            Some(libc::ENOENT) | Some(libc::ESRCH) => NoviMemError::ProcessGone { pid },
+            Some(libc::EIO) | Some(libc::EFAULT) => NoviMemError::UnmappedAddress(addr),
+            _ => NoviMemError::Io(e),
+        }
+    }
+
+    /// Whether a scan can carry on with the next region after this error
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            NoviMemError::ShortRead { .. } | NoviMemError::UnmappedAddress(_)
+        )
+    }
+}
+
+impl fmt::Display for NoviMemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NoviMemError::PermissionDenied { pid } => {
+                write!(f, "permission denied accessing memory of pid {}", pid)
+            }
+            NoviMemError::ProcessGone { pid } => write!(f, "process {} no longer exists", pid),
+            NoviMemError::ShortRead {
+                addr,
+                expected,
+                read,
+            } => write!(
+                f,
+                "short read at address {:X}: wanted {} bytes, got {}",
+                addr, expected, read
+            ),
+            NoviMemError::UnmappedAddress(addr) => write!(f, "address {:X} is not mapped", addr),
+            NoviMemError::Parse(msg) => write!(f, "parse error: {}", msg),
+            NoviMemError::NoSnapshot => write!(f, "no snapshot has been taken yet"),
+            NoviMemError::ReadOnly => write!(f, "memory source is read-only"),
+            NoviMemError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for NoviMemError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            NoviMemError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NoviMemError {
+    fn from(e: io::Error) -> Self {
+        NoviMemError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for NoviMemError {
+    fn from(e: serde_json::Error) -> Self {
+        NoviMemError::Parse(e.to_string())
+    }
+}
@@ -0,0 +1,139 @@
+use super::{
+    backend::MemoryBackend,
+    error::{NoviMemError, Result},
+    value::{DataType, Value},
+};
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// A value kept at `addr` by rewriting it over and over
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frozen {
+    pub addr: u64,
+    pub dtype: DataType,
+    pub value: Value,
+    /// Why the last rewrite failed, if it did
+    pub error: Option<String>,
+}
+
+/// Write all of `val` at `addr`
+pub(crate) fn write_all(backend: &dyn MemoryBackend, addr: u64, val: &[u8]) -> Result<()> {
+    let written = backend.write(addr, val)?;
+    if written == val.len() {
+        Ok(())
+    } else {
+        Err(NoviMemError::UnmappedAddress(addr + written as u64))
+    }
+}
+
+/// Owns the thread rewriting frozen values. The thread starts with the first frozen value
+/// and stops when the `Freezer` is dropped.
+pub struct Freezer {
+    backend: Arc<dyn MemoryBackend>,
+    entries: Arc<Mutex<Vec<Frozen>>>,
+    interval: Duration,
+    /// Sends interval changes; dropping it stops the thread
+    control: Option<Sender<Duration>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Freezer {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+    /// Shorter intervals would keep the thread rewriting values, and holding the
+    /// entries, almost all of the time
+    pub const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+    pub fn new(backend: Arc<dyn MemoryBackend>) -> Freezer {
+        Freezer {
+            backend,
+            entries: Arc::new(Mutex::new(Vec::new())),
+            interval: Freezer::DEFAULT_INTERVAL,
+            control: None,
+            handle: None,
+        }
+    }
+
+    /// Write `value` at `addr` now and keep rewriting it, replacing any value frozen there
+    pub fn freeze(&mut self, addr: u64, dtype: DataType, value: Value) -> Result<()> {
+        write_all(&*self.backend, addr, &dtype.encode(value))?;
+        {
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|f| f.addr != addr);
+            entries.push(Frozen {
+                addr,
+                dtype,
+                value,
+                error: None,
+            });
+        }
+        if self.handle.is_none() {
+            self.start();
+        }
+        Ok(())
+    }
+
+    pub fn unfreeze(&mut self, addr: u64) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let len = entries.len();
+        entries.retain(|f| f.addr != addr);
+        entries.len() != len
+    }
+
+    pub fn unfreeze_all(&mut self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub fn frozen(&self) -> Vec<Frozen> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Rewrite values every `interval`, at least `MIN_INTERVAL`
+    pub fn set_interval(&mut self, interval: Duration) {
+        let interval = interval.max(Freezer::MIN_INTERVAL);
+        self.interval = interval;
+        if let Some(control) = &self.control {
+            // A send only fails when the thread is gone, which `Drop` takes care of
+            let _ = control.send(interval);
+        }
+    }
+
+    fn start(&mut self) {
+        let (control, rx) = mpsc::channel();
+        let backend = Arc::clone(&self.backend);
+        let entries = Arc::clone(&self.entries);
+        let mut interval = self.interval;
+        self.control = Some(control);
+        self.handle = Some(thread::spawn(move || loop {
+            match rx.recv_timeout(interval) {
+                Ok(new_interval) => interval = new_interval,
+                Err(RecvTimeoutError::Timeout) => {
+                    for f in entries.lock().unwrap().iter_mut() {
+                        f.error = write_all(&*backend, f.addr, &f.dtype.encode(f.value))
+                            .err()
+                            .map(|e| e.to_string());
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }));
+    }
+}
+
+impl Drop for Freezer {
+    fn drop(&mut self) {
+        self.control.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
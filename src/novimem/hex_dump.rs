@@ -0,0 +1,189 @@
+use super::{
+    error::{NoviMemError, Result},
+    value::DataType,
+    NoviMem,
+};
+use std::mem::size_of;
+
+/// Bytes shown per row
+const ROW_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// What `dump` shows after each row's address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpView {
+    /// Hex words and the bytes as ASCII
+    Hex,
+    /// Decoded values of a type
+    Typed(DataType),
+    /// One pointer-sized value per row
+    Pointer,
+}
+
+#[derive(Debug, Clone)]
+pub struct HexDump {
+    /// Bytes per hex word: 1, 2, 4 or 8
+    pub word_size: usize,
+    pub endian: Endian,
+    pub view: DumpView,
+}
+
+impl Default for HexDump {
+    fn default() -> Self {
+        HexDump {
+            word_size: 1,
+            endian: Endian::Little,
+            view: DumpView::Hex,
+        }
+    }
+}
+
+impl HexDump {
+    pub fn set_word_size(&mut self, word_size: usize) -> Result<()> {
+        match word_size {
+            1 | 2 | 4 | 8 => {
+                self.word_size = word_size;
+                Ok(())
+            }
+            _ => Err(NoviMemError::Parse(format!(
+                "word size must be 1, 2, 4 or 8, not {}",
+                word_size
+            ))),
+        }
+    }
+
+    /// `bytes` in little-endian order, whatever the dump's endianness
+    fn to_le(&self, bytes: &[u8]) -> Vec<u8> {
+        match self.endian {
+            Endian::Little => bytes.to_vec(),
+            Endian::Big => bytes.iter().rev().copied().collect(),
+        }
+    }
+
+    fn row_len(&self) -> usize {
+        match self.view {
+            DumpView::Pointer => size_of::<u64>(),
+            _ => ROW_LEN,
+        }
+    }
+
+    /// Render `buf`, read from `addr`, as rows
+    pub fn format(&self, mem: &NoviMem, addr: u64, buf: &[u8]) -> Vec<String> {
+        buf.chunks(self.row_len())
+            .enumerate()
+            .map(|(i, row)| {
+                let row_addr = addr + (i * self.row_len()) as u64;
+                let body = match self.view {
+                    DumpView::Hex => self.hex_row(row),
+                    DumpView::Typed(dtype) => row
+                        .chunks_exact(dtype.size())
+                        .map(|v| format!("{:>12}", dtype.decode(&self.to_le(v)).to_string()))
+                        .collect::<Vec<String>>()
+                        .join(" "),
+                    DumpView::Pointer => row
+                        .get(..size_of::<u64>())
+                        .map(|v| format!("{:016X}", self.pointer(v)))
+                        .unwrap_or_default(),
+                };
+                format!("{:016X}  {}{}", row_addr, body, self.annotations(mem, row))
+            })
+            .collect()
+    }
+
+    fn hex_row(&self, row: &[u8]) -> String {
+        let words: Vec<String> = row
+            .chunks(self.word_size)
+            .map(|w| {
+                // Print the most significant byte first
+                let le = self.to_le(w);
+                le.iter().rev().map(|b| format!("{:02X}", b)).collect()
+            })
+            .collect();
+        let width = ROW_LEN / self.word_size * (self.word_size * 2 + 1);
+        let ascii: String = row
+            .iter()
+            .map(|b| {
+                if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        format!("{:<width$} |{:<16}|", words.join(" "), ascii, width = width)
+    }
+
+    fn pointer(&self, bytes: &[u8]) -> u64 {
+        let mut arr = [0u8; 8];
+        arr.copy_from_slice(&self.to_le(bytes));
+        u64::from_le_bytes(arr)
+    }
+
+    /// Where the pointer-sized values of `row` point, for those that point into a region
+    fn annotations(&self, mem: &NoviMem, row: &[u8]) -> String {
+        row.chunks_exact(size_of::<u64>())
+            .filter_map(|v| {
+                let ptr = self.pointer(v);
+                mem.get_containing_region(ptr)
+                    .map(|(start, name)| format!("  -> {}+{:X}", name, ptr - start))
+            })
+            .collect()
+    }
+}
+
+impl NoviMem {
+    /// Read `len` bytes at `addr` and render them as `dump` rows
+    pub fn hex_dump(&self, addr: u64, len: usize, dump: &HexDump) -> Result<Vec<String>> {
+        let buf = self.getval(addr, len)?;
+        Ok(dump.format(self, addr, &buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::novimem::backend::mock::MockBackend;
+
+    #[test]
+    fn test_hex_dump() {
+        let mut backend = MockBackend::new();
+        let mut heap = vec![0u8; 0x40];
+        heap[..4].copy_from_slice(b"Hi!\0");
+        heap[4..8].copy_from_slice(&2.5f32.to_le_bytes());
+        heap[8..16].copy_from_slice(&0x1020u64.to_le_bytes());
+        backend.add_region("[heap]", 0x1000, heap);
+        let m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+
+        let mut dump = HexDump::default();
+        let rows = m.hex_dump(0x1000, 20, &dump).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0],
+            "0000000000001000  48 69 21 00 00 00 20 40 20 10 00 00 00 00 00 00  \
+             |Hi!... @ .......|  -> [heap]+20"
+        );
+        assert!(rows[1].starts_with("0000000000001010  00 00 00 00"));
+
+        dump.set_word_size(4).unwrap();
+        assert!(dump.set_word_size(3).is_err());
+        let row = &m.hex_dump(0x1000, 8, &dump).unwrap()[0];
+        assert!(row.starts_with("0000000000001000  00216948 40200000"));
+        dump.endian = Endian::Big;
+        let row = &m.hex_dump(0x1000, 8, &dump).unwrap()[0];
+        assert!(row.starts_with("0000000000001000  48692100 00002040"));
+
+        dump.endian = Endian::Little;
+        dump.view = DumpView::Typed(DataType::F32);
+        let row = &m.hex_dump(0x1004, 4, &dump).unwrap()[0];
+        assert_eq!(row, "0000000000001004           2.5");
+
+        dump.view = DumpView::Pointer;
+        let rows = m.hex_dump(0x1000, 16, &dump).unwrap();
+        assert_eq!(rows[1], "0000000000001008  0000000000001020  -> [heap]+20");
+    }
+}
@@ -1,99 +1,511 @@
+pub mod backend;
+pub mod cheat_table;
+pub mod config;
+pub mod error;
+pub mod freeze;
+pub mod hex_dump;
 pub mod mem_image;
+pub mod pattern;
+pub mod pause;
+pub mod pointer_scan;
 pub mod proc_search;
+pub mod region_filter;
+pub mod region_reader;
+pub mod result_set;
+pub mod snapshot;
+pub mod text;
+pub mod value;
 
+use backend::{
+    dump_dir::{DumpEntry, DumpManifest, MANIFEST_NAME},
+    proc_mem::ProcMemBackend,
+    MemoryBackend,
+};
+use cheat_table::CheatTable;
+use error::{NoviMemError, Result};
+use freeze::{Freezer, Frozen};
+use pattern::Pattern;
+use pause::PauseGuard;
+use pointer_scan::PointerChain;
+use rayon::prelude::*;
+use regex::bytes::{Regex, RegexBuilder};
+use region_filter::{RegionFilter, RegionMatcher};
+use region_reader::CHUNK_SIZE;
+use result_set::{Bitmap, ResultSet};
 use serde::{Deserialize, Serialize};
-use serde_json::{from_str, to_string};
+use snapshot::{SnapshotOptions, SnapshotWriter, Snapshots};
 use std::{
+    borrow::Cow,
     collections::HashMap,
-    fs::read,
     fs::File,
-    fs::OpenOptions,
-    io::{prelude::*, BufReader, Seek, SeekFrom, Write},
-    u64,
+    io::{ErrorKind, Write},
+    os::unix::fs::FileExt,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
+use text::{Encoding, TextSearch};
+use value::{DataType, Value, ValueMatch};
 
-use rayon::prelude::*;
-
-#[derive(Debug, Clone)]
-struct MemRegion {
-    start_addr: u64,
-    end_addr: u64,
-    size: usize,
-    readable: bool,
-    writeable: bool,
-    execable: bool,
-    private: bool,
-    shared: bool,
-    name: String,
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemRegion {
+    pub start_addr: u64,
+    pub end_addr: u64,
+    pub size: usize,
+    pub readable: bool,
+    pub writeable: bool,
+    pub execable: bool,
+    pub private: bool,
+    pub shared: bool,
+    pub name: String,
+    /// Offset of the mapping within the mapped file
+    #[serde(default)]
+    pub file_offset: u64,
+    /// The mapped file or a pseudo-path such as `[heap]`, empty for anonymous memory
+    #[serde(default)]
+    pub pathname: String,
 }
 
 impl MemRegion {
-    pub fn dump_to_file(&self, buf: &[u8]) {
-        let mut f = File::create(format!(
+    pub fn dump_file_name(&self) -> String {
+        format!(
             "{:X}.{:X}.{:X}.dump",
             self.start_addr, self.size, self.end_addr
-        ))
-        .unwrap();
-        f.write_all(buf).unwrap();
-        f.flush().unwrap();
+        )
+    }
+
+    pub fn dump_to_file(&self, dir: &Path, buf: &[u8]) -> Result<()> {
+        let mut f = File::create(dir.join(self.dump_file_name()))?;
+        f.write_all(buf)?;
+        f.flush()?;
+        Ok(())
+    }
+}
+
+/// A result's location relative to the mapping it is in, so it can be found again
+/// after a restart moved everything around (ASLR)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchResult {
+    /// Pathname of the mapping, e.g. a library or `[heap]`. Empty for anonymous memory,
+    /// which cannot be found again, so `offset` then holds the absolute address.
+    pub module: String,
+    /// Which of the module's mappings, in address order
+    pub mapping: usize,
+    /// Offset into the mapped file, or into the mapping for pseudo-paths
+    pub offset: u64,
+}
+
+/// How the address space changed between two reads of its regions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionChanges {
+    pub added: Vec<MemRegion>,
+    pub removed: Vec<MemRegion>,
+    /// (old, new) of regions that kept their start and name but not their end
+    pub resized: Vec<(MemRegion, MemRegion)>,
+    /// Results that were dropped because their region went away
+    pub dropped_results: usize,
+}
+
+impl RegionChanges {
+    pub fn diff(old: &[MemRegion], new: &[MemRegion]) -> RegionChanges {
+        let same = |a: &MemRegion, b: &MemRegion| a.start_addr == b.start_addr && a.name == b.name;
+        let mut changes = RegionChanges::default();
+        for region in new {
+            match old.iter().find(|o| same(o, region)) {
+                Some(o) if o.end_addr != region.end_addr => {
+                    changes.resized.push((o.clone(), region.clone()))
+                }
+                Some(_) => {}
+                None => changes.added.push(region.clone()),
+            }
+        }
+        changes.removed = old
+            .iter()
+            .filter(|o| !new.iter().any(|r| same(o, r)))
+            .cloned()
+            .collect();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.resized.is_empty()
+            && self.dropped_results == 0
+    }
+}
+
+/// Where a regex search matched, relative to the result's address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexHit {
+    pub len: usize,
+    /// Offset and length of each capture group; group 0 is the whole match
+    pub groups: Vec<Option<(usize, usize)>>,
+}
+
+/// Characters of context printed on either side of a string hit
+const TEXT_CONTEXT_CHARS: usize = 16;
+
+/// Bytes repeated between chunks for regexes whose match length is not known
+const REGEX_OVERLAP: usize = 4096;
+
+/// Alignments a scan can be forced to
+fn check_alignment(alignment: usize) -> Result<usize> {
+    match alignment {
+        1 | 2 | 4 | 8 => Ok(alignment),
+        a => Err(NoviMemError::Parse(format!(
+            "alignment must be 1, 2, 4 or 8, not {}",
+            a
+        ))),
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct SearchResult {
-    region_key: u64,
-    offset: usize,
-    address: u64,
+fn mapped_in(regions: &[MemRegion], addr: u64) -> bool {
+    let idx = regions.partition_point(|r| r.end_addr <= addr);
+    regions.get(idx).is_some_and(|r| r.start_addr <= addr)
 }
 
-#[derive(Clone)]
-struct SnapShot {
-    region_key: u64,
-    data: Vec<u8>,
+/// The items of `items` whose index is not in the ascending `indices`
+fn remove_indices<'a, T, I>(items: I, indices: &'a [usize]) -> impl Iterator<Item = T> + 'a
+where
+    I: Iterator<Item = T> + 'a,
+{
+    let mut indices = indices.iter().peekable();
+    items.enumerate().filter_map(move |(idx, item)| {
+        if indices.peek() == Some(&&idx) {
+            indices.next();
+            None
+        } else {
+            Some(item)
+        }
+    })
 }
 
-impl PartialEq for SnapShot {
-    fn eq(&self, other: &Self) -> bool {
-        self.region_key == other.region_key
+/// Whether a match at `start..end` begins after the last one kept, which ended at
+/// `seen_to`. Chunks are searched separately, so one may find a match inside another's.
+fn non_overlapping(seen_to: &mut u64, start: u64, end: u64) -> bool {
+    if start < *seen_to {
+        return false;
     }
+    *seen_to = end;
+    true
 }
 
-impl Eq for SnapShot {}
+/// Offsets into a buffer starting at `base` where a `size` byte value at an
+/// `align`-aligned address fits entirely
+fn aligned_offsets(
+    base: u64,
+    len: usize,
+    size: usize,
+    align: usize,
+) -> impl Iterator<Item = usize> + Clone {
+    let first = ((align as u64 - base % align as u64) % align as u64) as usize;
+    (first..(len + 1).saturating_sub(size)).step_by(align)
+}
 
 pub struct NoviMem {
-    pid: u32,
     pname: String,
     regions: Vec<MemRegion>,
-    searches: HashMap<String, Vec<u64>>,
-    results: Vec<u64>,
+    /// What the last refresh of `regions` changed
+    region_changes: RegionChanges,
+    region_filter: RegionMatcher,
+    /// Stop the target while scanning it
+    pause_scans: bool,
+    searches: HashMap<String, Vec<SearchResult>>,
+    results: ResultSet,
     values: Vec<u8>,
-    snapshots: Vec<SnapShot>,
-    memfile: File,
+    /// The previous value of each result is in the current snapshots rather than `values`
+    values_in_snapshots: bool,
+    /// Set when the results are text hits, for printing them as strings
+    text_hits: Option<(Encoding, usize)>,
+    /// One entry per result when the results came from a regex search
+    regex_hits: Vec<RegexHit>,
+    scan_type: DataType,
+    scan_align: usize,
+    alignment: Option<usize>,
+    /// The scanned memory as of the last snapshot, per readable run of each region
+    snapshots: Snapshots,
+    snapshot_options: SnapshotOptions,
+    /// Bytes of a region read at once
+    chunk_size: usize,
+    pointer_chains: Vec<PointerChain>,
+    freezer: Freezer,
+    cheat_table: CheatTable,
+    backend: Arc<dyn MemoryBackend>,
 }
 
+/// How a value must have moved since the previous snapshot to be kept
+#[derive(Debug, Clone, Copy)]
 pub enum SearchType {
     Changed,
     Unchanged,
+    Increased,
+    Decreased,
+    IncreasedBy(Value),
+    DecreasedBy(Value),
+    /// The value moved in either direction by at least this much
+    ChangedByAtLeast(Value),
+    /// The value moved by an amount within `a..=b` (negative amounts are decreases)
+    Between(Value, Value),
+}
+
+impl SearchType {
+    pub fn matches(&self, dtype: DataType, prev: &[u8], cur: &[u8]) -> bool {
+        let size = dtype.size();
+        match self {
+            // Compare raw bytes so NaNs and -0.0 still count as (un)changed
+            SearchType::Changed => prev[..size] != cur[..size],
+            SearchType::Unchanged => prev[..size] == cur[..size],
+            _ => {
+                let (prev, cur) = (dtype.decode(prev), dtype.decode(cur));
+                let delta = cur.delta(prev);
+                let magnitude = prev.as_f64().abs().max(cur.as_f64().abs());
+                match *self {
+                    SearchType::Increased => cur > prev,
+                    SearchType::Decreased => cur < prev,
+                    SearchType::IncreasedBy(n) => delta.approx_eq(n, dtype, magnitude),
+                    SearchType::DecreasedBy(n) => prev.delta(cur).approx_eq(n, dtype, magnitude),
+                    SearchType::ChangedByAtLeast(n) => delta.abs() >= n.abs(),
+                    SearchType::Between(a, b) => delta >= a && delta <= b,
+                    SearchType::Changed | SearchType::Unchanged => unreachable!(),
+                }
+            }
+        }
+    }
 }
 
 impl NoviMem {
-    pub fn new(pid: u32, pname: String) -> NoviMem {
-        let mut m = NoviMem {
-            pid,
+    pub fn new(pid: u32, pname: String) -> Result<NoviMem> {
+        NoviMem::with_backend(Box::new(ProcMemBackend::new(pid)?), pname)
+    }
+
+    /// Scan any memory source, e.g. `ProcessVmBackend` or a `MockBackend` in tests
+    pub fn with_backend(backend: Box<dyn MemoryBackend>, pname: String) -> Result<NoviMem> {
+        let backend: Arc<dyn MemoryBackend> = Arc::from(backend);
+        Ok(NoviMem {
             pname,
-            regions: Vec::new(),
+            regions: backend.regions()?,
+            region_changes: RegionChanges::default(),
+            region_filter: RegionMatcher::default(),
+            pause_scans: false,
             searches: HashMap::new(),
-            results: Vec::new(),
+            results: ResultSet::default(),
             values: Vec::new(),
-            snapshots: Vec::new(),
-            memfile: NoviMem::open_mem(pid),
+            values_in_snapshots: false,
+            text_hits: None,
+            regex_hits: Vec::new(),
+            scan_type: DataType::U8,
+            scan_align: 1,
+            alignment: None,
+            snapshots: Snapshots::default(),
+            snapshot_options: SnapshotOptions::default(),
+            chunk_size: CHUNK_SIZE,
+            pointer_chains: Vec::new(),
+            freezer: Freezer::new(Arc::clone(&backend)),
+            cheat_table: CheatTable::default(),
+            backend,
+        })
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.backend.pid()
+    }
+
+    pub fn pname(&self) -> &str {
+        &self.pname
+    }
+
+    /// Every region of the address space
+    pub fn regions(&self) -> &[MemRegion] {
+        &self.regions
+    }
+
+    /// Re-read the regions of the address space. Results in regions that are gone
+    /// are dropped, as are the snapshots of those regions.
+    pub fn refresh_regions(&mut self) -> Result<&RegionChanges> {
+        let regions = self.backend.regions()?;
+        let mut changes = RegionChanges::diff(&self.regions, &regions);
+        self.regions = regions;
+        self.snapshots.retain(|run| {
+            !changes
+                .removed
+                .iter()
+                .any(|r| r.start_addr <= run.region_key && run.region_key < r.end_addr)
+        });
+
+        // Values and regex hits are kept per result, so trimming them needs the indices
+        // that went. Results with neither, which includes every compact set, are counted.
+        let regions = &self.regions;
+        let track = !self.values.is_empty() || !self.regex_hits.is_empty();
+        let mut dropped = Vec::new();
+        changes.dropped_results = self.results.retain(
+            |r| mapped_in(regions, r),
+            |idx| {
+                if track {
+                    dropped.push(idx)
+                }
+            },
+        );
+        if !dropped.is_empty() {
+            let stride = self.scan_type.size();
+            if !self.values.is_empty() {
+                self.values = remove_indices(self.values.chunks(stride), &dropped)
+                    .flatten()
+                    .copied()
+                    .collect();
+            }
+            if !self.regex_hits.is_empty() {
+                self.regex_hits = remove_indices(self.regex_hits.drain(..), &dropped).collect();
+            }
+        }
+        self.region_changes = changes;
+        Ok(&self.region_changes)
+    }
+
+    /// What the last refresh changed; scans refresh the regions before they start
+    pub fn region_changes(&self) -> &RegionChanges {
+        &self.region_changes
+    }
+
+    /// Take what the last refresh changed, so a later command does not report it again
+    pub fn take_region_changes(&mut self) -> RegionChanges {
+        std::mem::take(&mut self.region_changes)
+    }
+
+    fn is_mapped(&self, addr: u64) -> bool {
+        mapped_in(&self.regions, addr)
+    }
+
+    /// The regions searches, snapshots and pointer scans look at, as chosen by the
+    /// region filter: by default writeable data outside the stack
+    pub fn scan_regions(&self) -> impl Iterator<Item = &MemRegion> {
+        let matcher = &self.region_filter;
+        self.regions.iter().filter(move |r| matcher.matches(r))
+    }
+
+    pub fn region_filter(&self) -> &RegionFilter {
+        self.region_filter.filter()
+    }
+
+    pub fn set_region_filter(&mut self, filter: RegionFilter) -> Result<()> {
+        self.region_filter = RegionMatcher::new(filter)?;
+        Ok(())
+    }
+
+    /// Also scan executable regions, e.g. to find code signatures
+    pub fn set_include_exec(&mut self, include_exec: bool) {
+        self.region_filter.set_exec(include_exec);
+    }
+
+    pub fn include_exec(&self) -> bool {
+        self.region_filter.filter().exec
+    }
+
+    /// Read regions `size` bytes at a time (at least one page), bounding the memory
+    /// a scan holds beyond its results
+    pub fn set_chunk_size(&mut self, size: usize) {
+        self.chunk_size = size.max(region_reader::PAGE_SIZE as usize);
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// How much of a snapshot to keep in memory and where the rest goes. Takes effect
+    /// from the next snapshot.
+    pub fn set_snapshot_options(&mut self, options: SnapshotOptions) {
+        self.snapshot_options = options;
+    }
+
+    pub fn snapshot_options(&self) -> &SnapshotOptions {
+        &self.snapshot_options
+    }
+
+    /// Bytes of the current snapshot held in memory and on disk
+    pub fn snapshot_usage(&self) -> (usize, usize) {
+        self.snapshots.usage()
+    }
+
+    /// Stop the target with SIGSTOP while scanning it, so values cannot change
+    /// between the reads of one scan. Has no effect without a live process.
+    pub fn set_pause_scans(&mut self, pause_scans: bool) {
+        self.pause_scans = pause_scans;
+    }
+
+    pub fn pause_scans(&self) -> bool {
+        self.pause_scans
+    }
+
+    /// Stop the target if scans should pause it; it resumes when the guard is dropped
+    pub(crate) fn pause_target(&self) -> Result<Option<PauseGuard>> {
+        match self.backend.pid() {
+            Some(pid) if self.pause_scans => PauseGuard::new(pid).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Dump every readable region into `dir` along with a manifest,
+    /// so the capture can be scanned later through `DumpDirBackend`. Regions are read a
+    /// chunk at a time; pages that fail to read are left out of the manifest, which lists
+    /// each readable run of a region. Returns the number of runs.
+    pub fn dump_to_dir(&self, dir: &Path) -> Result<usize> {
+        std::fs::create_dir_all(dir)?;
+        let mut manifest = DumpManifest {
+            pname: self.pname.clone(),
+            regions: Vec::new(),
         };
-        m.parse_maps();
-        m
+        for region in self.regions.iter().filter(|r| r.readable) {
+            let file_name = region.dump_file_name();
+            let mut file: Option<File> = None;
+            let first_entry = manifest.regions.len();
+            for start in (region.start_addr..region.end_addr).step_by(self.chunk_size) {
+                let len = (region.end_addr - start).min(self.chunk_size as u64) as usize;
+                for (addr, data) in self.read_runs(start, len)? {
+                    let offset = addr - region.start_addr;
+                    let f = match file {
+                        Some(ref f) => f,
+                        None => file.insert(File::create(dir.join(&file_name))?),
+                    };
+                    f.write_all_at(&data, offset)?;
+                    let end = addr + data.len() as u64;
+                    match manifest.regions[first_entry..].last_mut() {
+                        // Runs read by consecutive chunks join up
+                        Some(e) if e.region.end_addr == addr => {
+                            e.region.end_addr = end;
+                            e.region.size += data.len();
+                        }
+                        _ => {
+                            let mut run = region.clone();
+                            run.start_addr = addr;
+                            run.end_addr = end;
+                            run.size = data.len();
+                            if !run.pathname.is_empty() {
+                                run.file_offset += offset;
+                            }
+                            manifest.regions.push(DumpEntry {
+                                region: run,
+                                file: file_name.clone(),
+                                file_offset: offset,
+                            });
+                        }
+                    }
+                }
+            }
+            // Unreadable pages read back as zeros from the file on its own
+            if let Some(f) = file {
+                f.set_len(region.size as u64)?;
+            }
+        }
+        let mut f = File::create(dir.join(MANIFEST_NAME))?;
+        f.write_all(serde_json::to_string(&manifest)?.as_bytes())?;
+        Ok(manifest.regions.len())
     }
 
     pub fn print_modules(&self) {
-        self.regions.iter().for_each(|region| {
+        self.scan_regions().for_each(|region| {
             println!(
                 "{:X}:{:X}\t{}",
                 region.start_addr, region.end_addr, region.name
@@ -101,380 +513,1192 @@ impl NoviMem {
         });
     }
 
-    pub fn save_searches_to_file(&self) {
+    pub fn save_searches_to_file(&self) -> Result<()> {
         if !self.searches.is_empty() {
-            let json = serde_json::to_string(&self.searches).unwrap();
-            // write(format!("{}.searches", self.pname), json).unwrap();
-            let fname = format!("./{}.searches", self.pname);
-            match File::create(&fname.to_string()) {
-                Ok(mut f) => {
-                    f.write(json.to_string().as_bytes()).unwrap();
-                }
-                Err(e) => println!(
-                    "Unable to create file '{}' in save_searches_to_file(): {}",
-                    &fname, e
-                ),
-            };
+            let json = serde_json::to_string(&self.searches)?;
+            let mut f = File::create(format!("./{}.searches", self.pname))?;
+            f.write_all(json.as_bytes())?;
         }
+        Ok(())
     }
 
-    pub fn load_searches_from_file(&mut self) {
-        if let Ok(f) = read(format!("./{}.searches", self.pname)) {
-            let json: String = String::from_utf8_lossy(&f).to_string();
-            if !json.is_empty() {
-                self.searches = serde_json::from_str(&json).unwrap();
+    pub fn load_searches_from_file(&mut self) -> Result<()> {
+        match std::fs::read(format!("./{}.searches", self.pname)) {
+            Ok(f) => {
+                let json: String = String::from_utf8_lossy(&f).to_string();
+                if !json.is_empty() {
+                    self.searches = match serde_json::from_str(&json) {
+                        Ok(searches) => searches,
+                        // Searches saved as plain absolute addresses
+                        Err(e) => serde_json::from_str::<HashMap<String, Vec<u64>>>(&json)
+                            .map_err(|_| e)?
+                            .into_iter()
+                            .map(|(name, addrs)| {
+                                let results = addrs
+                                    .into_iter()
+                                    .map(|offset| SearchResult {
+                                        module: String::new(),
+                                        mapping: 0,
+                                        offset,
+                                    })
+                                    .collect();
+                                (name, results)
+                            })
+                            .collect(),
+                    };
+                }
+                Ok(())
             }
+            // Nothing saved for this process yet
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
         }
     }
 
-    pub fn save_search(&mut self, name: String) {
-        self.searches.insert(name, self.results.to_owned());
-        self.results.clear();
+    pub fn save_search(&mut self, name: String) -> Result<()> {
+        let results = self.results.iter().map(|a| self.search_result(a)).collect();
+        self.searches.insert(name, results);
+        self.clear_results();
         self.save_searches_to_file()
     }
 
-    pub fn restore_search(&mut self, name: String) -> bool {
-        if let Some(result) = self.searches.get(&name) {
-            self.results = result.to_vec();
-            true
-        } else {
-            false
+    /// Make a saved search the current results, re-based onto the current regions.
+    /// Returns how many of its results are no longer mapped and were left out.
+    pub fn restore_search(&mut self, name: String) -> Option<usize> {
+        let saved = self.searches.get(&name)?;
+        let results: Vec<u64> = saved.iter().filter_map(|r| self.rebase(r)).collect();
+        let dropped = saved.len() - results.len();
+        self.set_results(results.into(), Vec::new());
+        Some(dropped)
+    }
+
+    /// Describe `addr` relative to its mapping
+    pub fn search_result(&self, addr: u64) -> SearchResult {
+        let idx = self.regions.partition_point(|r| r.end_addr <= addr);
+        match self.regions.get(idx) {
+            Some(region) if region.start_addr <= addr && !region.pathname.is_empty() => {
+                SearchResult {
+                    module: region.pathname.clone(),
+                    mapping: self.regions[..idx]
+                        .iter()
+                        .filter(|r| r.pathname == region.pathname)
+                        .count(),
+                    offset: region.file_offset + (addr - region.start_addr),
+                }
+            }
+            _ => SearchResult {
+                module: String::new(),
+                mapping: 0,
+                offset: addr,
+            },
         }
     }
 
+    /// Find where `result` is in the current regions: in the same mapping of its module if
+    /// that still covers the offset, else in whichever of the module's mappings does
+    pub fn rebase(&self, result: &SearchResult) -> Option<u64> {
+        if result.module.is_empty() {
+            return Some(result.offset);
+        }
+        let covers = |r: &&MemRegion| {
+            r.file_offset <= result.offset && r.file_offset + r.size as u64 > result.offset
+        };
+        let mut mappings = self.regions.iter().filter(|r| r.pathname == result.module);
+        mappings
+            .clone()
+            .nth(result.mapping)
+            .filter(covers)
+            .or_else(|| mappings.find(covers))
+            .map(|r| r.start_addr + (result.offset - r.file_offset))
+    }
+
     pub fn delete_search(&mut self, name: String) -> bool {
-        if self.searches.remove(&name).is_some() {
-            true
-        } else {
-            false
+        self.searches.remove(&name).is_some()
+    }
+
+    fn set_results(&mut self, results: ResultSet, values: Vec<u8>) {
+        self.results = results;
+        self.values = values;
+        self.values_in_snapshots = false;
+        self.text_hits = None;
+        self.regex_hits.clear();
+    }
+
+    /// Results of a snapshot comparison, whose values are in the snapshots just taken.
+    /// Only results too many to list keep them there rather than in `values`.
+    fn set_snapshot_results(&mut self, snapshots: Snapshots, results: ResultSet) -> Result<()> {
+        let size = self.scan_type.size();
+        let mut values = Vec::new();
+        if !results.is_compact() {
+            for a in results.iter() {
+                if let Some(v) = snapshots.bytes(a, size)? {
+                    values.extend_from_slice(&v);
+                }
+            }
         }
+        self.snapshots = snapshots;
+        self.set_results(results, values);
+        self.values_in_snapshots = self.results.is_compact();
+        Ok(())
     }
 
     pub fn clear_results(&mut self) {
-        self.results.clear();
+        self.set_results(ResultSet::default(), Vec::new());
+    }
+
+    pub fn searches(&self) -> impl Iterator<Item = &String> {
+        self.searches.keys()
     }
 
     pub fn print_searches(&self) {
-        self.searches.keys().for_each(|name| println!("\t{}", name));
+        self.searches().for_each(|name| println!("\t{}", name));
     }
 
-    pub fn setval(&mut self, addr: u64, val: &[u8]) {
-        self.memfile.seek(SeekFrom::Start(addr)).unwrap();
-        if self.memfile.write(val).is_err() {
-            println!("Unable to write val at address {:X}", addr);
-        }
+    pub fn setval(&self, addr: u64, val: &[u8]) -> Result<()> {
+        freeze::write_all(&*self.backend, addr, val)
     }
 
-    pub fn getval(&mut self, addr: u64, size: usize) -> Option<Vec<u8>> {
-        if self.memfile.seek(SeekFrom::Start(addr)).is_ok() {
-            let mut reader = BufReader::with_capacity(size as usize, &self.memfile);
-            match reader.fill_buf() {
-                Ok(buf) => Some(buf.to_vec()),
-                Err(e) => {
-                    println!(
-                        "Unable to fill buffer in getval() at address {:X} with size {}: {}",
-                        addr, size, e
-                    );
-                    None
-                }
+    /// Keep writing `value` at `addr` from a background thread until unfrozen
+    pub fn freeze(&mut self, addr: u64, dtype: DataType, value: Value) -> Result<()> {
+        self.freezer.freeze(addr, dtype, value)
+    }
+
+    pub fn unfreeze(&mut self, addr: u64) -> bool {
+        self.freezer.unfreeze(addr)
+    }
+
+    pub fn unfreeze_all(&mut self) {
+        self.freezer.unfreeze_all()
+    }
+
+    pub fn frozen(&self) -> Vec<Frozen> {
+        self.freezer.frozen()
+    }
+
+    pub fn freeze_interval(&self) -> Duration {
+        self.freezer.interval()
+    }
+
+    /// How often frozen values are rewritten, at least every millisecond
+    pub fn set_freeze_interval(&mut self, interval: Duration) {
+        self.freezer.set_interval(interval)
+    }
+
+    pub fn getval(&self, addr: u64, size: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; size];
+        let mut read = 0;
+        while read < size {
+            match self.backend.read(addr + read as u64, &mut buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if read == 0 => return Err(e),
+                Err(_) => break,
             }
+        }
+        if read == size {
+            Ok(buf)
         } else {
-            println!("Unable to seek to address {:X} in getval()", addr);
-            None
+            Err(NoviMemError::ShortRead {
+                addr,
+                expected: size,
+                read,
+            })
         }
     }
 
-    // pub fn get_region(&self, addr: u64) -> Option<&MemRegion> {
-    //     let found: Vec<MemRegion> = self
-    //         .regions
-    //         .into_iter()
-    //         .filter(|region| region.start_addr <= addr && region.end_addr >= addr)
-    //         .collect();
-    //     if found.len() == 1 {
-    //         found.first()
-    //     } else {
-    //         None
-    //     }
-    // }
-
-    // pub fn get_region_contents(&mut self, region_key: &str) -> Option<Vec<u8>> {
-    //     if let Some(region) = self.regions.get(region_key) {
-    //         self.getval(region.start_addr, region.size)
-    //     } else {
-    //         println!("Unable to find region {}", region_key);
-    //         None
-    //     }
-    // }
-
     pub fn get_containing_region(&self, addr: u64) -> Option<(u64, &String)> {
-        if let Some((addr, name)) = self
-            .regions
+        self.regions
             .iter()
-            .filter_map(|r| {
-                if r.start_addr <= addr && r.end_addr >= addr {
-                    Some((r.start_addr, &r.name))
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<(u64, &String)>>()
-            .first()
-        {
-            Some((*addr, name))
-        } else {
-            None
+            .find(|r| r.start_addr <= addr && r.end_addr > addr)
+            .map(|r| (r.start_addr, &r.name))
+    }
+
+    /// Bytes at `addr` as recorded in the previous snapshot
+    fn snapshot_bytes(&self, addr: u64, size: usize) -> Result<Option<Cow<'_, [u8]>>> {
+        self.snapshots.bytes(addr, size)
+    }
+
+    /// Force every value search and snapshot scan to this alignment (1, 2, 4 or 8),
+    /// or go back to the natural alignment of the searched type with `None`
+    pub fn set_alignment(&mut self, alignment: Option<usize>) -> Result<()> {
+        self.alignment = alignment.map(check_alignment).transpose()?;
+        Ok(())
+    }
+
+    pub fn alignment(&self) -> Option<usize> {
+        self.alignment
+    }
+
+    /// The alignment scans for a value of `size` bytes use
+    pub fn alignment_for(&self, size: usize) -> usize {
+        match (self.alignment, size) {
+            (Some(a), _) => a,
+            (None, 1) | (None, 2) | (None, 4) | (None, 8) => size,
+            (None, _) => 1,
         }
     }
 
-    pub fn take_snapshots(&mut self, stype: Option<SearchType>) -> usize {
-        // Get the current snapshot of all regions
-        let mut snapshots = Vec::<SnapShot>::with_capacity(self.regions.len());
-        self.regions.clone().iter().for_each(|r| {
-            if let Some(data) = self.getval(r.start_addr, r.size) {
-                snapshots.push(SnapShot {
-                    region_key: r.start_addr,
-                    data: data,
-                });
-            }
-        });
-        let mut resvec: Vec<u64> = Vec::new();
-        let mut values: Vec<u8> = Vec::new();
+    /// Start an unknown-initial-value scan: values are `dtype` wide and only
+    /// addresses that are a multiple of `alignment` become candidates.
+    pub fn init_snapshots(&mut self, dtype: DataType, alignment: usize) -> Result<usize> {
+        self.scan_align = check_alignment(alignment)?;
+        self.scan_type = dtype;
+        self.clear_results();
+        self.take_snapshots(None)
+    }
+
+    /// Snapshot every region. With a search type, keep the addresses whose value
+    /// moved as described since the previous snapshot (or only re-check existing results).
+    /// Values are decoded as the type given to `init_snapshots`.
+    pub fn take_snapshots(&mut self, stype: Option<SearchType>) -> Result<usize> {
+        self.refresh_regions()?;
+        let _paused = self.pause_target()?;
+        // Get the current snapshot of all regions, spilling chunks to disk past the
+        // memory budget
+        let writer = SnapshotWriter::new(&self.snapshot_options, &self.snapshots);
+        let blocks = self
+            .scan_chunks(0, |chunk| writer.store(chunk))?
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        let snapshots = writer.finish(blocks)?;
+        let (dtype, align) = (self.scan_type, self.scan_align);
+        let size = dtype.size();
+        let mut results = ResultSet::default();
 
         if let Some(t) = stype {
-            if !self.snapshots.is_empty() {
-                // We have a search type specified and we have a previous snapshot
-                // Only retain the snapshots that exist in both
-                // snapshots.retain(|s| self.snapshots.contains(s));
-                // TODO: This may become more complex in the future
-                let should_equal = match t {
-                    SearchType::Changed => false,
-                    SearchType::Unchanged => true,
-                };
-                if self.results.is_empty() {
-                    // Compare our new snapshot with the existing snapshots
-                    // For each snapshot, use our chosen compare method
-                    // to decide which addresses to add to our results
-                    snapshots.iter().for_each(|s| {
-                        // For each byte in this snapshot's data, compare with
-                        // the comparable self.snapshot's data
-                        if let Some(idx) = self
-                            .snapshots
-                            .iter()
-                            .position(|prev_snap| s.region_key == prev_snap.region_key)
-                        {
-                            let prev_snap = &self.snapshots.clone()[idx];
-                            // Now we have our previous snapshot and our existing snapshot -- let's compare the data
-                            // and save off the indeces where they match
-
-                            resvec.extend(
-                                prev_snap
-                                    .data
-                                    .iter()
-                                    .zip(&s.data)
-                                    .enumerate()
-                                    .filter_map(|(i, (a, b))| {
-                                        if (*a == *b) == should_equal {
-                                            values.push(*a);
-                                            Some(i as u64 + s.region_key)
-                                        } else {
-                                            None
-                                        }
-                                    })
-                                    .collect::<Vec<u64>>(),
-                            );
-                        } else {
-                            println!(
-                            "Snapshot contains region not included in existing snapshots...huh?"
-                        );
-                        }
-                    });
-                } else {
-                    // We have results, search through them instead
-                    resvec = self
-                        .results()
-                        .clone()
+            if self.snapshots.is_empty() {
+                return Err(NoviMemError::NoSnapshot);
+            }
+            // We have a search type specified and we have a previous snapshot
+            if self.results.is_empty() {
+                // Compare the bytes each new snapshot shares with the previous ones,
+                // a chunk at a time. Memory that was not readable last time has
+                // nothing to compare against.
+                // Pieces follow the chunks the new snapshot was stored in, so each
+                // is read from disk or inflated once.
+                let mut pieces = Vec::new();
+                for s in snapshots.runs() {
+                    for p in self
+                        .snapshots
+                        .runs()
                         .iter()
-                        .zip(self.values.clone())
-                        .enumerate()
-                        .filter_map(|(i, (a, v))| {
-                            if let Some(val) = self.getval(*a, 1) {
-                                if let Some(val) = val.first() {
-                                    if (*val == v) == should_equal {
-                                        values.push(*val);
-                                        Some(*a)
-                                    } else {
-                                        None
-                                    }
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
+                        .filter(|p| p.region_key < s.end() && s.region_key < p.end())
+                    {
+                        let start = s.region_key.max(p.region_key);
+                        let end = s.end().min(p.end());
+                        for (lo, hi) in s.block_ranges() {
+                            let piece = lo.max(start);
+                            if piece >= hi.min(end) {
+                                continue;
                             }
-                        })
-                        .collect();
+                            // Values starting in this piece may run into the next one
+                            let piece_end = (hi + size as u64 - 1).min(end);
+                            pieces.push((piece, piece_end, s, p));
+                        }
+                    }
                 }
+                // Use our chosen compare method to decide which addresses to add to our results
+                let found = pieces
+                    .par_iter()
+                    .map(|(piece, piece_end, s, p)| {
+                        let cur = snapshots.range(s, *piece, *piece_end)?;
+                        let prev = self.snapshots.range(p, *piece, *piece_end)?;
+                        let offsets = aligned_offsets(*piece, cur.len(), size, align);
+                        let mut found = Bitmap::for_offsets(*piece, align, offsets.clone());
+                        for (slot, i) in offsets.enumerate() {
+                            if t.matches(dtype, &prev[i..i + size], &cur[i..i + size]) {
+                                found.set(slot);
+                            }
+                        }
+                        Ok(found)
+                    })
+                    .collect::<Result<Vec<Bitmap>>>()?;
+                results = ResultSet::from_bitmaps(found);
             } else {
-                println!("Search type specified, but no snapshot currently exists!");
+                // We have results, search through them instead.
+                // Each result keeps its whole previous value; results that came from
+                // a value search have none, so fall back to the previous snapshot.
+                let recorded =
+                    !self.values_in_snapshots && self.values.len() == self.results.len() * size;
+                results = self
+                    .results
+                    .par_filter_map(|idx, a| {
+                        let prev = if recorded {
+                            Some(Cow::Borrowed(&self.values[idx * size..(idx + 1) * size]))
+                        } else {
+                            self.snapshot_bytes(a, size)?
+                        };
+                        Ok(prev
+                            .zip(snapshots.bytes(a, size)?)
+                            .filter(|(prev, cur)| t.matches(dtype, prev, cur))
+                            .map(|_| ()))
+                    })?
+                    .0;
             }
         }
-        self.snapshots = snapshots;
-        self.results = resvec;
-        self.values = values;
-        self.results.len()
+        self.set_snapshot_results(snapshots, results)?;
+        Ok(self.results.len())
+    }
+
+    /// The type and alignment of the current unknown-value scan
+    pub fn scan_type(&self) -> (DataType, usize) {
+        (self.scan_type, self.scan_align)
+    }
+
+    /// The previous value recorded for result `idx`, decoded as the scan type. Values
+    /// kept in a snapshot that can no longer be read are left out.
+    pub fn result_value(&self, idx: usize) -> Option<Value> {
+        let size = self.scan_type.size();
+        if self.values_in_snapshots {
+            return self
+                .snapshot_bytes(self.results.get(idx)?, size)
+                .ok()
+                .flatten()
+                .map(|v| self.scan_type.decode(&v));
+        }
+        if self.values.len() != self.results.len() * size {
+            return None;
+        }
+        self.values
+            .get(idx * size..(idx + 1) * size)
+            .map(|v| self.scan_type.decode(v))
+    }
+
+    /// The text around result `idx` when the results are string hits
+    pub fn result_context(&self, idx: usize) -> Option<String> {
+        let (encoding, len) = self.text_hits?;
+        let addr = self.results.get(idx)?;
+        let region = self
+            .regions
+            .iter()
+            .find(|r| r.start_addr <= addr && r.end_addr > addr)?;
+        let margin = (TEXT_CONTEXT_CHARS * encoding.unit()) as u64;
+        let start = addr.saturating_sub(margin).max(region.start_addr);
+        let end = (addr + len as u64 + margin).min(region.end_addr);
+        let buf = self.getval(start, (end - start) as usize).ok()?;
+        Some(text::context(encoding, &buf, (addr - start) as usize, len))
+    }
+
+    fn format_regex_hit(&self, addr: u64, hit: &RegexHit) -> String {
+        let buf = match self.getval(addr, hit.len) {
+            Ok(buf) => buf,
+            Err(_) => return format!(" len {}", hit.len),
+        };
+        let mut out = format!(" len {}", hit.len);
+        hit.groups.iter().enumerate().for_each(|(i, g)| match g {
+            Some((start, len)) => {
+                let text = buf.get(*start..start + len).unwrap_or_default();
+                out.push_str(&format!(" ${}=\"{}\"", i, text.escape_ascii()))
+            }
+            None => out.push_str(&format!(" ${}=<none>", i)),
+        });
+        out
     }
 
     pub fn print_results(&self) {
-        self.results.iter().for_each(|result| {
-            if let Some((region_addr, region_name)) = self.get_containing_region(*result) {
+        if self.results.is_compact() {
+            println!("\t{} results, too many to list", self.results.len());
+            return;
+        }
+        self.results.iter().enumerate().for_each(|(idx, result)| {
+            let value = match (self.result_value(idx), self.result_context(idx)) {
+                (_, Some(text)) => format!(" \"{}\"", text),
+                (Some(v), None) => format!(" = {}", v),
+                (None, None) => match self.regex_hit(idx) {
+                    Some(hit) => self.format_regex_hit(result, hit),
+                    None => String::new(),
+                },
+            };
+            if let Some((region_addr, region_name)) = self.get_containing_region(result) {
                 println!(
-                    "\t{:X} ({:X} + {:X} in {})",
+                    "\t{:X} ({:X} + {:X} in {}){}",
                     result,
                     region_addr,
                     result - region_addr,
-                    region_name
+                    region_name,
+                    value
                 );
             } else {
-                println!("\t{:X}", result);
+                println!("\t{:X}{}", result, value);
             }
         });
         println!("\t{} results", self.results.len());
     }
 
-    pub fn results(&self) -> &Vec<u64> {
+    pub fn results(&self) -> &ResultSet {
         &self.results
     }
 
-    pub fn search(&mut self, val: &[u8]) -> usize {
-        // Explicitly use the bytes regex
-        use regex::bytes::RegexBuilder;
-        let mut memfile = self.memfile.try_clone().unwrap();
-        let mut valstr = String::new();
-        val.iter()
-            .for_each(|b| valstr.push_str(&format!("\\x{:02x}", b).to_string()));
-        println!("Searching for {}", valstr);
-        let mut builder = RegexBuilder::new(&valstr.to_string());
-        builder
-            .unicode(false)
-            .dot_matches_new_line(true)
-            .case_insensitive(false);
-        if let Ok(re) = builder.build() {
-            let mut results = Vec::new();
+    /// Search for the exact bytes `val` at the configured (or natural) alignment
+    pub fn search(&mut self, val: &[u8]) -> Result<usize> {
+        let align = self.alignment_for(val.len());
+        self.search_pattern(&Pattern::from_bytes(val), align)
+    }
+
+    /// Search for an array-of-bytes pattern at `align`-aligned addresses,
+    /// either over every region or only at the existing results
+    pub fn search_pattern(&mut self, pattern: &Pattern, align: usize) -> Result<usize> {
+        let re = pattern.to_regex()?;
+        self.refresh_regions()?;
+        let _paused = self.pause_target()?;
+        let len = pattern.len();
+        let results = if self.results.is_empty() {
             // If this is a new search, look through everything
-            if self.results.is_empty() {
-                self.regions.iter().for_each(|region| {
-                    // Fill the buffer with this module's memory by seeking to the start address first
-                    if memfile.seek(SeekFrom::Start(region.start_addr)).is_ok() {
-                        let mut reader = BufReader::with_capacity(region.size, &memfile);
-                        if let Ok(buf) = reader.fill_buf() {
-                            re.find_iter(buf)
-                                .for_each(|m| results.push(region.start_addr + m.start() as u64));
-                        } else {
-                            println!(
-                                "Unable to fill buffer from memory region {} :-(",
-                                region.name
-                            );
-                        }
-                    } else {
-                        println!(
-                            "Unable to seek to region {} start address {:X}",
-                            region.name, region.start_addr
-                        );
-                    }
-                })
+            if align == 1 {
+                self.scan_regex(&re, len.saturating_sub(1))?
             } else {
-                // Otherwise, only look through our existing results
-                let results_cpy = self.results.clone();
-                results = results_cpy
-                    .iter()
-                    .filter_map(|r| {
-                        if let Some(read_val) = self.getval(*r, val.len()) {
-                            if read_val == val {
-                                Some(*r)
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
+                // Only aligned offsets can match, so compare them directly
+                let found = self.scan_chunks(len.saturating_sub(1), |chunk| {
+                    aligned_offsets(chunk.addr, chunk.data.len(), len, align)
+                        .filter(|i| chunk.owns(*i) && pattern.matches(&chunk.data[*i..]))
+                        .map(|i| chunk.addr + i as u64)
+                        .collect::<Vec<u64>>()
+                })?;
+                ResultSet::from(found.into_iter().flatten().collect::<Vec<u64>>())
             }
-            self.results = results;
         } else {
-            println!("Unable to build search :-(");
-        }
-        self.results.len()
-    }
-
-    fn open_mem(pid: u32) -> File {
-        OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(false)
-            .open(format!("/proc/{}/mem", pid))
-            .unwrap_or_else(|_| panic!("Unable to open memory for pid {}", pid))
-    }
-
-    fn parse_maps(&mut self) {
-        use regex::RegexBuilder;
-        let mapsfile = OpenOptions::new()
-            .read(true)
-            .write(false)
-            .create(false)
-            .open(format!("/proc/{}/maps", self.pid))
-            .expect("Unable to open file");
-        let regex_str =
-            //address 1,2                    perms 3,4,5,6            offset           dev                           inode     pathname 7
-            r"([0-9A-Fa-f]+)-([0-9A-Fa-f]+) ([-r])([-w])([-x])([-ps]) (?:[0-9A-Fa-f]+) (?:[0-9A-Fa-f]+:[0-9A-Fa-f]+) (?:\d+)\s+(.*)?";
-        let mut builder = RegexBuilder::new(regex_str);
-        builder
-            .unicode(true)
-            .dot_matches_new_line(true)
-            .case_insensitive(false);
-        // Parse the maps file to find regions of interest
-        match builder.build() {
-            Ok(re) => {
-                BufReader::new(mapsfile).lines().for_each(|line| {
-                    if let Ok(resline) = line {
-                        if let Some(cap) = re.captures(resline.as_str()) {
-                            if cap.len() > 0 {
-                                let start = u64::from_str_radix(&cap[1], 16).unwrap();
-                                let end = u64::from_str_radix(&cap[2], 16).unwrap();
-                                let region = MemRegion {
-                                    start_addr: start,
-                                    end_addr: end,
-                                    size: (end - start) as usize,
-                                    readable: &cap[3] == "r",
-                                    writeable: &cap[4] == "w",
-                                    execable: &cap[5] == "x",
-                                    private: &cap[6] == "p",
-                                    shared: &cap[6] == "s",
-                                    name: if let Some(n) = cap.get(7) {
-                                        let name = n.as_str().to_string();
-                                        if name.is_empty() {
-                                            format!("{:X}", start)
-                                        } else {
-                                            name.replace('\0', "")
-                                        }
-                                    } else {
-                                        println!("Failed to capture module name {}", resline);
-                                        format!("{:X}", start)
-                                    },
-                                };
-                                self.regions.push(region);
-                            } else {
-                                println!("Did not include maps line {}", resline);
-                            }
-                        } else {
-                            println!("Failed to parse {}", &resline)
+            // Otherwise, only look through our existing results
+            self.refine_results(len, |buf| pattern.matches(buf))?
+        };
+        self.set_results(results, Vec::new());
+        Ok(self.results.len())
+    }
+
+    /// Search for a string, either over every region or only at the existing results
+    pub fn search_text(&mut self, search: &TextSearch) -> Result<usize> {
+        let re = search.to_regex()?;
+        let len = search.encoded_len();
+        self.refresh_regions()?;
+        let _paused = self.pause_target()?;
+        let results = if self.results.is_empty() {
+            self.scan_regex(&re, REGEX_OVERLAP)?
+        } else {
+            self.refine_results(len, |buf| re.find(buf).is_some_and(|m| m.start() == 0))?
+        };
+        self.set_results(results, Vec::new());
+        self.text_hits = Some((search.encoding, len));
+        Ok(self.results.len())
+    }
+
+    /// Search with a user-supplied regex. With `text` the pattern matches UTF-8 text
+    /// (`.` is a character, `\w` is Unicode-aware); without it, it matches raw bytes
+    /// (`\x00` is a byte). Each result keeps its match length and capture groups.
+    pub fn search_regex(&mut self, pattern: &str, text: bool) -> Result<usize> {
+        let re = RegexBuilder::new(pattern)
+            .unicode(text)
+            .dot_matches_new_line(!text)
+            .build()
+            .map_err(|e| NoviMemError::Parse(e.to_string()))?;
+        let hit = |caps: &regex::bytes::Captures, start: usize| RegexHit {
+            len: caps[0].len(),
+            groups: caps
+                .iter()
+                .map(|g| g.map(|g| (g.start() - start, g.end() - g.start())))
+                .collect(),
+        };
+        self.refresh_regions()?;
+        let _paused = self.pause_target()?;
+        let (results, hits): (ResultSet, Vec<RegexHit>) = if self.results.is_empty() {
+            let found = self.scan_chunks(REGEX_OVERLAP, |chunk| {
+                re.captures_iter(&chunk.data)
+                    .filter_map(|caps| {
+                        let m = caps.get(0).unwrap();
+                        chunk.owns(m.start()).then(|| {
+                            (
+                                chunk.addr + m.start() as u64,
+                                chunk.addr + m.end() as u64,
+                                hit(&caps, m.start()),
+                            )
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })?;
+            let mut seen_to = 0;
+            let (results, hits): (Vec<u64>, Vec<RegexHit>) = found
+                .into_iter()
+                .flatten()
+                .filter(|(start, end, _)| non_overlapping(&mut seen_to, *start, *end))
+                .map(|(start, _, hit)| (start, hit))
+                .unzip();
+            (results.into(), hits)
+        } else {
+            // Re-match each result within the extent of its previous match
+            self.map_results(
+                |idx| self.regex_hits.get(idx).map_or(1, |h| h.len.max(1)),
+                |_, _, buf| {
+                    re.captures(buf)
+                        .filter(|c| c.get(0).unwrap().start() == 0)
+                        .map(|caps| hit(&caps, 0))
+                },
+            )?
+        };
+        self.set_results(results, Vec::new());
+        self.regex_hits = hits;
+        Ok(self.results.len())
+    }
+
+    /// Match length and capture groups of result `idx`, if it came from a regex search
+    pub fn regex_hit(&self, idx: usize) -> Option<&RegexHit> {
+        self.regex_hits.get(idx)
+    }
+
+    /// Addresses of every match of `re` in the scanned regions. Matches must fit in
+    /// `overlap + 1` bytes to be found across the boundary between two chunks.
+    fn scan_regex(&self, re: &Regex, overlap: usize) -> Result<ResultSet> {
+        let found = self.scan_chunks(overlap, |chunk| {
+            re.find_iter(&chunk.data)
+                .filter(|m| chunk.owns(m.start()))
+                .map(|m| (chunk.addr + m.start() as u64, chunk.addr + m.end() as u64))
+                .collect::<Vec<(u64, u64)>>()
+        })?;
+        let mut seen_to = 0;
+        let results: Vec<u64> = found
+            .into_iter()
+            .flatten()
+            .filter(|(start, end)| non_overlapping(&mut seen_to, *start, *end))
+            .map(|(start, _)| start)
+            .collect();
+        Ok(results.into())
+    }
+
+    /// The existing results whose `len` bytes satisfy `keep`
+    fn refine_results<F>(&self, len: usize, keep: F) -> Result<ResultSet>
+    where
+        F: Fn(&[u8]) -> bool + Sync,
+    {
+        Ok(self
+            .map_results(|_| len, |_, _, buf| keep(buf).then_some(()))?
+            .0)
+    }
+
+    /// Read `len(idx)` bytes at each result on the thread pool and keep the results `f`
+    /// gives a value for, along with those values. Results that cannot be read are dropped.
+    fn map_results<R, L, F>(&self, len: L, f: F) -> Result<(ResultSet, Vec<R>)>
+    where
+        R: Send,
+        L: Fn(usize) -> usize + Sync,
+        F: Fn(usize, u64, &[u8]) -> Option<R> + Sync,
+    {
+        self.results
+            .par_filter_map(|idx, r| match self.getval(r, len(idx)) {
+                Ok(buf) => Ok(f(idx, r, &buf)),
+                Err(e) if e.is_recoverable() => Ok(None),
+                Err(e) => Err(e),
+            })
+    }
+
+    /// Search for `dtype` values satisfying `vmatch`, either over every region or,
+    /// when there are results, only at the existing results.
+    /// The values found are kept with the results, typed as `dtype`, unless there are
+    /// too many to list; later comparisons then use the previous snapshot instead.
+    pub fn search_value(&mut self, dtype: DataType, vmatch: &ValueMatch) -> Result<usize> {
+        let size = dtype.size();
+        let align = self.alignment_for(size);
+        self.refresh_regions()?;
+        let _paused = self.pause_target()?;
+        let (results, values) = if self.results.is_empty() {
+            // Values are only kept for a set small enough to become a list. Once more than
+            // that were found, chunks stop recording them and the set stays compact.
+            let total = AtomicUsize::new(0);
+            let found = self.scan_chunks(size - 1, |chunk| {
+                let offsets = aligned_offsets(chunk.addr, chunk.data.len(), size, align)
+                    .take_while(|i| chunk.owns(*i));
+                let mut found = Bitmap::for_offsets(chunk.addr, align, offsets.clone());
+                let mut values = Vec::new();
+                for (slot, i) in offsets.enumerate() {
+                    let cur = &chunk.data[i..i + size];
+                    if vmatch.matches(dtype, dtype.decode(cur)) {
+                        found.set(slot);
+                        if total.fetch_add(1, Ordering::Relaxed) < result_set::LIST_LIMIT {
+                            values.extend_from_slice(cur);
                         }
                     }
-                });
+                }
+                (found, values)
+            })?;
+            let (found, values): (Vec<Bitmap>, Vec<Vec<u8>>) = found.into_iter().unzip();
+            let results = ResultSet::from_bitmaps(found);
+            let values = if results.is_compact() {
+                Vec::new()
+            } else {
+                values.concat()
+            };
+            (results, values)
+        } else {
+            let (results, values) = self.map_results(
+                |_| size,
+                |_, _, cur| {
+                    vmatch
+                        .matches(dtype, dtype.decode(cur))
+                        .then(|| cur.to_vec())
+                },
+            )?;
+            (results, values.concat())
+        };
+        self.set_results(results, values);
+        self.scan_type = dtype;
+        Ok(self.results.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::backend::mock::MockBackend;
+    use super::*;
+    use std::process;
+
+    fn mock_mem() -> NoviMem {
+        let mut backend = MockBackend::new();
+        let mut heap = vec![0u8; 0x100];
+        heap[0x10..0x14].copy_from_slice(&1234u32.to_le_bytes());
+        heap[0x80..0x84].copy_from_slice(&1234u32.to_le_bytes());
+        backend.add_region("[heap]", 0x1000, heap);
+        backend.add_region("[stack]", 0x8000, vec![0u8; 0x100]);
+        NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap()
+    }
+
+    #[test]
+    fn test_search_mock() {
+        let mut m = mock_mem();
+        assert_eq!(m.scan_regions().count(), 1);
+        assert_eq!(m.search(&1234u32.to_le_bytes()).unwrap(), 2);
+        assert_eq!(m.results(), &vec![0x1010, 0x1080]);
+
+        m.setval(0x1080, &99u32.to_le_bytes()).unwrap();
+        assert_eq!(m.search(&1234u32.to_le_bytes()).unwrap(), 1);
+        assert_eq!(m.results(), &vec![0x1010]);
+    }
+
+    #[test]
+    fn test_snapshots_mock() {
+        let mut m = mock_mem();
+        m.take_snapshots(None).unwrap();
+        m.setval(0x1020, &[7]).unwrap();
+        assert_eq!(m.take_snapshots(Some(SearchType::Changed)).unwrap(), 1);
+        assert_eq!(m.results(), &vec![0x1020]);
+        assert!(m.getval(0x10F0, 0x20).is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let pid = process::id();
+        let mut m = NoviMem::new(pid, String::from("novimem")).unwrap();
+        let x = Box::new(0xDEAD_BEEF_DEAD_BEEF_DEAD_BEEF_1234_5678u128);
+        let addr = &*x as *const u128 as u64;
+        m.search(&x.to_le_bytes()).unwrap();
+        assert!(m.results().contains(addr));
+
+        // Rewrite the value through the memory file and refine the search
+        let newval = 0xDEAD_C0DE_DEAD_C0DE_DEAD_C0DE_1234_5678u128;
+        m.setval(addr, &newval.to_le_bytes()).unwrap();
+        assert_eq!(*x, newval);
+        m.search(&newval.to_le_bytes()).unwrap();
+        assert!(m.results().contains(addr));
+    }
+
+    #[test]
+    fn test_snapshot_modes() {
+        let mut m = mock_mem();
+        m.init_snapshots(DataType::U32, 4).unwrap();
+        // 1234 -> 1240 at 0x1010, 1234 -> 1000 at 0x1080
+        m.setval(0x1010, &1240u32.to_le_bytes()).unwrap();
+        m.setval(0x1080, &1000u32.to_le_bytes()).unwrap();
+        let inc_by = DataType::U32.parse_delta("6").unwrap();
+        m.take_snapshots(Some(SearchType::IncreasedBy(inc_by)))
+            .unwrap();
+        assert_eq!(m.results(), &vec![0x1010]);
+        m.clear_results();
+
+        m.setval(0x1080, &900u32.to_le_bytes()).unwrap();
+        m.take_snapshots(Some(SearchType::Decreased)).unwrap();
+        assert_eq!(m.results(), &vec![0x1080]);
+
+        // Refining results compares against the values recorded with them
+        m.setval(0x1080, &850u32.to_le_bytes()).unwrap();
+        let (lo, hi) = (
+            DataType::U32.parse_delta("-60").unwrap(),
+            DataType::U32.parse_delta("-40").unwrap(),
+        );
+        assert_eq!(
+            m.take_snapshots(Some(SearchType::Between(lo, hi))).unwrap(),
+            1
+        );
+        assert_eq!(m.result_value(0), Some(Value::Int(850)));
+        let at_least = DataType::U32.parse_delta("1").unwrap();
+        assert_eq!(
+            m.take_snapshots(Some(SearchType::ChangedByAtLeast(at_least)))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_typed_unknown_value_scan() {
+        let mut backend = MockBackend::new();
+        let mut heap = vec![0u8; 0x20];
+        heap[0x8..0xC].copy_from_slice(&256u32.to_le_bytes());
+        backend.add_region("[heap]", 0x1000, heap);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+
+        // Byte-wise, 256 -> 255 changes two bytes and neither is the value's address
+        m.init_snapshots(DataType::U8, 1).unwrap();
+        m.setval(0x1008, &255u32.to_le_bytes()).unwrap();
+        m.take_snapshots(Some(SearchType::Changed)).unwrap();
+        assert_eq!(m.results(), &vec![0x1008, 0x1009]);
+
+        m.init_snapshots(DataType::U32, 4).unwrap();
+        m.setval(0x1008, &254u32.to_le_bytes()).unwrap();
+        m.take_snapshots(Some(SearchType::Decreased)).unwrap();
+        assert_eq!(m.results(), &vec![0x1008]);
+        assert_eq!(m.result_value(0), Some(Value::Int(254)));
+
+        // Unaligned candidates are only considered when asked for
+        m.init_snapshots(DataType::U32, 1).unwrap();
+        m.setval(0x1008, &0x0100_0000u32.to_le_bytes()).unwrap();
+        m.take_snapshots(Some(SearchType::Increased)).unwrap();
+        assert_eq!(m.results(), &vec![0x1008, 0x1009, 0x100A, 0x100B]);
+        assert!(m.init_snapshots(DataType::U32, 0).is_err());
+        assert!(m.init_snapshots(DataType::U32, 3).is_err());
+        assert!(m.set_alignment(Some(3)).is_err());
+    }
+
+    #[test]
+    fn test_float_snapshot_modes() {
+        let mut backend = MockBackend::new();
+        backend.add_region("[heap]", 0x1000, 0.5f32.to_le_bytes().to_vec());
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+        m.init_snapshots(DataType::F32, 4).unwrap();
+        m.setval(0x1000, &0.75f32.to_le_bytes()).unwrap();
+        let by = DataType::F32.parse_delta("0.25").unwrap();
+        m.take_snapshots(Some(SearchType::IncreasedBy(by))).unwrap();
+        assert_eq!(m.results(), &vec![0x1000]);
+
+        // At 100 an f32 is off by far more than the delta's own rounding error
+        m.setval(0x1000, &100.1f32.to_le_bytes()).unwrap();
+        m.init_snapshots(DataType::F32, 4).unwrap();
+        m.setval(0x1000, &100.2f32.to_le_bytes()).unwrap();
+        let by = DataType::F32.parse_delta("0.1").unwrap();
+        assert_eq!(
+            m.take_snapshots(Some(SearchType::IncreasedBy(by))).unwrap(),
+            1
+        );
+        m.setval(0x1000, &100.1f32.to_le_bytes()).unwrap();
+        assert_eq!(
+            m.take_snapshots(Some(SearchType::DecreasedBy(by))).unwrap(),
+            1
+        );
+        m.setval(0x1000, &100.2f32.to_le_bytes()).unwrap();
+        let by = DataType::F32.parse_delta("0.1001").unwrap();
+        assert_eq!(
+            m.take_snapshots(Some(SearchType::IncreasedBy(by))).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_range_search() {
+        let mut backend = MockBackend::new();
+        let mut heap = vec![0xFFu8; 0x20];
+        heap[0x0..0x4].copy_from_slice(&95u32.to_le_bytes());
+        heap[0x8..0xC].copy_from_slice(&0.75f32.to_le_bytes());
+        heap[0x10..0x14].copy_from_slice(&120u32.to_le_bytes());
+        backend.add_region("[heap]", 0x1000, heap);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+
+        let between = ValueMatch::parse(DataType::U32, "between", &["110", "90"]).unwrap();
+        assert_eq!(m.search_value(DataType::U32, &between).unwrap(), 1);
+        assert_eq!(m.results(), &vec![0x1000]);
+        assert_eq!(m.result_value(0), Some(Value::Int(95)));
+
+        // Refine: the value moved out of range
+        m.setval(0x1000, &111u32.to_le_bytes()).unwrap();
+        assert_eq!(m.search_value(DataType::U32, &between).unwrap(), 0);
+
+        let greater = ValueMatch::parse(DataType::F32, ">", &["0.5"]).unwrap();
+        m.search_value(DataType::F32, &greater).unwrap();
+        assert!(m.results().contains(0x1008));
+        let less = ValueMatch::parse(DataType::F32, "<=", &["1"]).unwrap();
+        m.search_value(DataType::F32, &less).unwrap();
+        assert_eq!(m.results(), &vec![0x1008]);
+        assert!(ValueMatch::parse(DataType::U8, "between", &["1"]).is_err());
+
+        // A health bar shown as "0.8" in the UI
+        m.clear_results();
+        let shown = ValueMatch::parse(DataType::F32, "~", &["0.8"]).unwrap();
+        m.search_value(DataType::F32, &shown).unwrap();
+        assert_eq!(m.results(), &vec![0x1008]);
+        let trunc = ValueMatch::parse(DataType::F32, "~", &["0.7", "trunc", "1"]).unwrap();
+        assert_eq!(m.search_value(DataType::F32, &trunc).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_aob_exec_regions() {
+        let mut backend = MockBackend::new();
+        let mut code = vec![0x90u8; 0x40];
+        code[0x11..0x19].copy_from_slice(&[0x48, 0x8B, 0x05, 0x78, 0x56, 0x34, 0x12, 0x89]);
+        code[0x19] = 0x4C;
+        let text = backend.add_region("/usr/bin/game", 0x40_0000, code);
+        text.writeable = false;
+        text.execable = true;
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+        let pattern = Pattern::parse("48 8B 05 ?? ?? ?? ?? 89 4?").unwrap();
+
+        assert_eq!(m.search_pattern(&pattern, 1).unwrap(), 0);
+        m.set_include_exec(true);
+        assert_eq!(m.search_pattern(&pattern, 1).unwrap(), 1);
+        assert_eq!(m.results(), &vec![0x40_0011]);
+    }
+
+    #[test]
+    fn test_text_search() {
+        let mut backend = MockBackend::new();
+        let mut heap = vec![0u8; 0x40];
+        heap[0x4..0xA].copy_from_slice(b"Player");
+        let wide = Encoding::Utf16Le.encode("PLAYER");
+        heap[0x20..0x20 + wide.len()].copy_from_slice(&wide);
+        backend.add_region("[heap]", 0x1000, heap);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+
+        let mut search = TextSearch::new("player", Encoding::Utf8);
+        assert_eq!(m.search_text(&search).unwrap(), 0);
+        search.case_insensitive = true;
+        search.null_terminated = true;
+        assert_eq!(m.search_text(&search).unwrap(), 1);
+        assert_eq!(
+            m.result_context(0).unwrap(),
+            "....[Player.]................"
+        );
+        m.clear_results();
+
+        search.encoding = Encoding::Utf16Le;
+        assert_eq!(m.search_text(&search).unwrap(), 1);
+        assert_eq!(m.results(), &vec![0x1020]);
+        // Refine after the string changed
+        m.setval(0x1020, &Encoding::Utf16Le.encode("Q")).unwrap();
+        assert_eq!(m.search_text(&search).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_regex_search() {
+        let mut backend = MockBackend::new();
+        let mut heap = vec![0u8; 0x60];
+        heap[0x8..0x21].copy_from_slice(b"url=https://example.com/a");
+        heap[0x30..0x3A].copy_from_slice(b"tok:\xDE\xAD\xBE\xEF\x00\x01");
+        backend.add_region("[heap]", 0x1000, heap);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+
+        assert_eq!(
+            m.search_regex(r"https?://([a-z.]+)(/\w*)?", true).unwrap(),
+            1
+        );
+        assert_eq!(m.results(), &vec![0x100C]);
+        let hit = m.regex_hit(0).unwrap().clone();
+        assert_eq!(hit.len, 21);
+        assert_eq!(
+            hit.groups,
+            vec![Some((0, 21)), Some((8, 11)), Some((19, 2))]
+        );
+        assert_eq!(
+            m.format_regex_hit(0x100C, &hit),
+            " len 21 $0=\"https://example.com/a\" $1=\"example.com\" $2=\"/a\""
+        );
+        m.clear_results();
+
+        assert_eq!(m.search_regex(r"tok:(\xDE\xAD.{2})", false).unwrap(), 1);
+        assert_eq!(m.regex_hit(0).unwrap().groups[1], Some((4, 4)));
+        // Refining keeps hits that still match where they were
+        assert_eq!(m.search_regex(r"tok:", false).unwrap(), 1);
+        m.setval(0x1030, b"TOK").unwrap();
+        assert_eq!(m.search_regex(r"tok:", false).unwrap(), 0);
+        assert!(m.search_regex(r"(", true).is_err());
+    }
+
+    #[test]
+    fn test_alignment() {
+        let mut backend = MockBackend::new();
+        let mut heap = vec![0u8; 0x20];
+        heap[0x4..0x8].copy_from_slice(&7u32.to_le_bytes());
+        heap[0x9..0xD].copy_from_slice(&7u32.to_le_bytes());
+        backend.add_region("[heap]", 0x1000, heap);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+
+        // Natural alignment of a u32 skips the unaligned copy
+        assert_eq!(m.search(&7u32.to_le_bytes()).unwrap(), 1);
+        m.clear_results();
+        let seven = ValueMatch::Exact(Value::Int(7));
+        assert_eq!(m.search_value(DataType::U32, &seven).unwrap(), 1);
+        m.clear_results();
+
+        m.set_alignment(Some(1)).unwrap();
+        assert_eq!(m.search(&7u32.to_le_bytes()).unwrap(), 2);
+        m.clear_results();
+        assert_eq!(m.search_value(DataType::U32, &seven).unwrap(), 2);
+        assert_eq!(m.alignment_for(4), 1);
+        assert!(m.set_alignment(Some(3)).is_err());
+        m.set_alignment(None).unwrap();
+        assert_eq!(m.alignment_for(8), 8);
+        assert_eq!(m.alignment_for(16), 1);
+    }
+
+    #[test]
+    fn test_process_vm_backend() {
+        use super::backend::process_vm::ProcessVmBackend;
+        let x = Box::new(0x0123_4567_89AB_CDEFu64);
+        let addr = &*x as *const u64 as u64;
+        let m = NoviMem::with_backend(
+            Box::new(ProcessVmBackend::new(process::id()).unwrap()),
+            String::from("novimem"),
+        )
+        .unwrap();
+        assert_eq!(m.getval(addr, 8).unwrap(), x.to_le_bytes());
+    }
+
+    #[test]
+    fn test_dump_dir_roundtrip() {
+        use super::backend::dump_dir::DumpDirBackend;
+        let dir = std::env::temp_dir().join(format!("novimem-dump-{}", process::id()));
+        let mut m = mock_mem();
+        // The capture keeps regions that are not scanned, like the stack
+        assert_eq!(m.dump_to_dir(&dir).unwrap(), 2);
+        let expected = m.search(&1234u32.to_le_bytes()).unwrap();
+
+        let backend = DumpDirBackend::open(&dir).unwrap();
+        assert_eq!(backend.pname(), "mock");
+        let mut offline = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+        assert_eq!(offline.regions()[1].name, "[stack]");
+        assert_eq!(offline.scan_regions().count(), 1);
+        assert_eq!(offline.search(&1234u32.to_le_bytes()).unwrap(), expected);
+        assert!(offline.setval(0x1010, &[0]).is_err());
+
+        // Bare dump files without a manifest are still readable
+        std::fs::remove_file(dir.join(MANIFEST_NAME)).unwrap();
+        let mut bare =
+            NoviMem::with_backend(Box::new(DumpDirBackend::open(&dir).unwrap()), String::new())
+                .unwrap();
+        assert_eq!(bare.regions()[0].name, "1000");
+        assert_eq!(bare.search(&1234u32.to_le_bytes()).unwrap(), expected);
+
+        // A name whose bounds overflow is a format error, not a panic
+        std::fs::write(dir.join("FFFFFFFFFFFFFFFF.10.F.dump"), [0u8; 0x10]).unwrap();
+        assert!(matches!(
+            DumpDirBackend::open(&dir),
+            Err(NoviMemError::Parse(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dump_unreadable_page() {
+        use super::backend::dump_dir::DumpDirBackend;
+        let dir = std::env::temp_dir().join(format!("novimem-dump-eio-{}", process::id()));
+        let backend = MockBackend::new();
+        let mut heap = vec![0u8; 0x4000];
+        heap[0x10..0x14].copy_from_slice(&1234u32.to_le_bytes());
+        heap[0x3010..0x3014].copy_from_slice(&1234u32.to_le_bytes());
+        backend.map_region("[heap]", 0x10000, heap);
+        backend.set_unreadable(0x11000);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+        m.set_chunk_size(0x1000);
+
+        // The readable pages are kept, around the one that fails
+        assert_eq!(m.dump_to_dir(&dir).unwrap(), 2);
+        let offline = DumpDirBackend::open(&dir).unwrap();
+        let runs: Vec<(u64, u64)> = offline
+            .regions()
+            .unwrap()
+            .iter()
+            .map(|r| (r.start_addr, r.end_addr))
+            .collect();
+        assert_eq!(runs, vec![(0x10000, 0x11000), (0x12000, 0x14000)]);
+        let mut offline = NoviMem::with_backend(Box::new(offline), String::new()).unwrap();
+        assert_eq!(offline.search(&1234u32.to_le_bytes()).unwrap(), 2);
+        assert_eq!(offline.results(), &vec![0x10010, 0x13010]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_freeze() {
+        let mut m = mock_mem();
+        m.set_freeze_interval(Duration::from_millis(2));
+        m.freeze(0x1010, DataType::U32, Value::Int(999)).unwrap();
+        assert_eq!(m.getval(0x1010, 4).unwrap(), 999u32.to_le_bytes());
+
+        m.setval(0x1010, &5u32.to_le_bytes()).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(m.getval(0x1010, 4).unwrap(), 999u32.to_le_bytes());
+        assert_eq!(m.frozen().len(), 1);
+        assert!(m.frozen()[0].error.is_none());
+
+        assert!(m.unfreeze(0x1010));
+        assert!(!m.unfreeze(0x1010));
+        m.setval(0x1010, &5u32.to_le_bytes()).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(m.getval(0x1010, 4).unwrap(), 5u32.to_le_bytes());
+        assert!(m.freeze(0x10_0000, DataType::U8, Value::Int(1)).is_err());
+
+        // A zero interval is raised to the minimum rather than spinning
+        m.set_freeze_interval(Duration::from_millis(0));
+        assert_eq!(m.freeze_interval(), Freezer::MIN_INTERVAL);
+        m.freeze(0x1010, DataType::U32, Value::Int(7)).unwrap();
+        m.setval(0x1010, &5u32.to_le_bytes()).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(m.getval(0x1010, 4).unwrap(), 7u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_restore_rebased() {
+        let game = |base: u64, heap_base: u64| {
+            let mut backend = MockBackend::new();
+            let mut data = vec![0u8; 0x100];
+            data[0x40..0x44].copy_from_slice(&777u32.to_le_bytes());
+            backend
+                .add_region("/usr/bin/game", base, vec![0u8; 0x100])
+                .writeable = false;
+            backend
+                .add_region("/usr/bin/game", base + 0x1000, data)
+                .file_offset = 0x1000;
+            let mut heap = vec![0u8; 0x100];
+            heap[0x10..0x14].copy_from_slice(&777u32.to_le_bytes());
+            backend.add_region("[heap]", heap_base, heap);
+            NoviMem::with_backend(Box::new(backend), String::from("game")).unwrap()
+        };
+        let mut m = game(0x40_0000, 0x10_0000);
+        assert_eq!(m.search(&777u32.to_le_bytes()).unwrap(), 2);
+        let saved: Vec<SearchResult> = m.results().iter().map(|a| m.search_result(a)).collect();
+        assert_eq!(
+            saved[1],
+            SearchResult {
+                module: "/usr/bin/game".to_string(),
+                mapping: 1,
+                offset: 0x1040,
             }
-            Err(e) => println!("ERR: Unable to build regex in parse_maps(): {}", e), // We only care about modules that are marked as executable
+        );
+
+        let mut restarted = game(0x5500_0000, 0x20_0000);
+        restarted.searches.insert("ammo".to_string(), saved);
+        assert_eq!(restarted.restore_search("ammo".to_string()), Some(0));
+        assert_eq!(restarted.results(), &vec![0x20_0010, 0x5500_1040]);
+        assert_eq!(restarted.restore_search("none".to_string()), None);
+    }
+
+    #[test]
+    fn test_refresh_regions() {
+        let backend = Arc::new(MockBackend::new());
+        backend.map_region("[heap]", 0x1000, 7u32.to_le_bytes().repeat(4));
+        backend.map_region("anon", 0x8000, 7u32.to_le_bytes().repeat(2));
+        let mut m =
+            NoviMem::with_backend(Box::new(Arc::clone(&backend)), String::from("mock")).unwrap();
+        let seven = ValueMatch::Exact(Value::Int(7));
+        assert_eq!(m.search_value(DataType::U32, &seven).unwrap(), 6);
+
+        // The heap grew, the anonymous mapping went away and a new one appeared
+        backend.unmap_region(0x1000);
+        backend.map_region("[heap]", 0x1000, 7u32.to_le_bytes().repeat(8));
+        backend.unmap_region(0x8000);
+        backend.map_region("anon", 0x9000, 7u32.to_le_bytes().to_vec());
+        let changes = m.refresh_regions().unwrap().clone();
+        assert_eq!(changes.added.len(), 1);
+        assert_eq!(changes.added[0].start_addr, 0x9000);
+        assert_eq!(changes.removed[0].start_addr, 0x8000);
+        assert_eq!(changes.resized[0].1.end_addr, 0x1020);
+        assert_eq!(changes.dropped_results, 2);
+        assert_eq!(m.results().len(), 4);
+        assert_eq!(m.result_value(3), Some(Value::Int(7)));
+        assert!(m.refresh_regions().unwrap().is_empty());
+
+        // New searches see the new memory without an explicit refresh
+        backend.map_region("late", 0xA000, 7u32.to_le_bytes().to_vec());
+        m.clear_results();
+        assert_eq!(m.search_value(DataType::U32, &seven).unwrap(), 10);
+        assert_eq!(m.region_changes().added.len(), 1);
+        assert_eq!(m.take_region_changes().added.len(), 1);
+        assert!(m.region_changes().added.is_empty());
+    }
+
+    #[test]
+    fn test_compact_results() {
+        let backend = Arc::new(MockBackend::new());
+        let len = result_set::LIST_LIMIT + 0x1000;
+        backend.map_region("[heap]", 0x10_0000, vec![5u8; len]);
+        backend.map_region("anon", 0x100_0000, vec![5u8; 0x10]);
+        let mut m =
+            NoviMem::with_backend(Box::new(Arc::clone(&backend)), String::from("mock")).unwrap();
+        m.init_snapshots(DataType::U8, 1).unwrap();
+        m.setval(0x10_0010, &[6]).unwrap();
+        assert_eq!(
+            m.take_snapshots(Some(SearchType::Unchanged)).unwrap(),
+            len + 0xF
+        );
+        assert!(m.results().is_compact());
+        assert!(!m.results().contains(0x10_0010));
+        assert_eq!(m.results().get(0x10), Some(0x10_0011));
+        assert_eq!(m.result_value(0), Some(Value::Int(5)));
+
+        // Results in a region that went away are dropped from the bitmaps too
+        backend.unmap_region(0x100_0000);
+        assert_eq!(m.refresh_regions().unwrap().dropped_results, 0x10);
+
+        // Few enough results become a list again, with their own values
+        m.setval(0x10_0020, &[9]).unwrap();
+        assert_eq!(m.take_snapshots(Some(SearchType::Increased)).unwrap(), 1);
+        assert!(!m.results().is_compact());
+        assert_eq!(m.results(), &vec![0x10_0020]);
+        m.setval(0x10_0020, &[5]).unwrap();
+        assert_eq!(m.result_value(0), Some(Value::Int(9)));
+        assert_eq!(m.take_snapshots(Some(SearchType::Decreased)).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_compact_value_search() {
+        let backend = Arc::new(MockBackend::new());
+        let len = result_set::LIST_LIMIT + 0x1000;
+        backend.map_region("[heap]", 0x10_0000, vec![5u8; len]);
+        let mut m =
+            NoviMem::with_backend(Box::new(Arc::clone(&backend)), String::from("mock")).unwrap();
+        let five = ValueMatch::Exact(Value::Int(5));
+        assert_eq!(m.search_value(DataType::U8, &five).unwrap(), len);
+        assert!(m.results().is_compact());
+        assert!(m.values.is_empty());
+        assert_eq!(m.result_value(0), None);
+
+        // Narrowed down to a list, the results record their values again
+        m.setval(0x10_0010, &[6]).unwrap();
+        let six = ValueMatch::Exact(Value::Int(6));
+        assert_eq!(m.search_value(DataType::U8, &six).unwrap(), 1);
+        assert_eq!(m.result_value(0), Some(Value::Int(6)));
+        backend.unmap_region(0x10_0000);
+        assert_eq!(m.refresh_regions().unwrap().dropped_results, 1);
+    }
+
+    #[test]
+    fn test_unmap_compact_region() {
+        let backend = Arc::new(MockBackend::new());
+        let len = result_set::LIST_LIMIT + 0x1000;
+        backend.map_region("[heap]", 0x10_0000, vec![5u8; len]);
+        backend.map_region("anon", 0x100_0000, vec![5u8; 0x10]);
+        let mut m =
+            NoviMem::with_backend(Box::new(Arc::clone(&backend)), String::from("mock")).unwrap();
+        m.init_snapshots(DataType::U8, 1).unwrap();
+        assert_eq!(
+            m.take_snapshots(Some(SearchType::Unchanged)).unwrap(),
+            len + 0x10
+        );
+        assert!(m.results().is_compact());
+
+        // Dropping the region behind the bitmaps counts what went without listing it
+        backend.unmap_region(0x10_0000);
+        assert_eq!(m.refresh_regions().unwrap().dropped_results, len);
+        assert_eq!(m.results().len(), 0x10);
+        assert!(!m.results().is_compact());
+        assert_eq!(m.results().get(0), Some(0x100_0000));
+    }
+
+    #[test]
+    fn test_getval_unmapped() {
+        let m = NoviMem::new(process::id(), String::from("novimem")).unwrap();
+        match m.getval(0, 8) {
+            Err(NoviMemError::UnmappedAddress(0)) => {}
+            other => panic!("unexpected {:?}", other.map(|_| ())),
         }
-        self.regions
-            .retain(|r| r.readable && r.writeable && r.name != "[stack]");
     }
 }
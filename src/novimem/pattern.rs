@@ -0,0 +1,133 @@
+use super::error::{NoviMemError, Result};
+use regex::bytes::{Regex, RegexBuilder};
+
+/// One byte of an array-of-bytes pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatternByte {
+    Exact(u8),
+    /// `4?`: the high nibble must match
+    High(u8),
+    /// `?4`: the low nibble must match
+    Low(u8),
+    /// `??` or `?`
+    Any,
+}
+
+impl PatternByte {
+    fn matches(self, b: u8) -> bool {
+        match self {
+            PatternByte::Exact(x) => b == x,
+            PatternByte::High(x) => b >> 4 == x,
+            PatternByte::Low(x) => b & 0xF == x,
+            PatternByte::Any => true,
+        }
+    }
+}
+
+/// A Cheat-Engine-style array-of-bytes pattern such as `48 8B 05 ?? ?? ?? ?? 89 4? 10`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    bytes: Vec<PatternByte>,
+}
+
+impl Pattern {
+    /// Parse whitespace-separated hex bytes, where `??`/`?` matches any byte
+    /// and `4?`/`?4` match on a single nibble
+    pub fn parse(s: &str) -> Result<Pattern> {
+        let nibble = |c: char, tok: &str| {
+            c.to_digit(16)
+                .map(|d| d as u8)
+                .ok_or_else(|| NoviMemError::Parse(format!("invalid pattern byte '{}'", tok)))
+        };
+        let bytes = s
+            .split_whitespace()
+            .map(|tok| {
+                let chars: Vec<char> = tok.chars().collect();
+                match chars.as_slice() {
+                    ['?'] | ['?', '?'] => Ok(PatternByte::Any),
+                    ['?', lo] => Ok(PatternByte::Low(nibble(*lo, tok)?)),
+                    [hi, '?'] => Ok(PatternByte::High(nibble(*hi, tok)?)),
+                    [hi, lo] => Ok(PatternByte::Exact(
+                        nibble(*hi, tok)? << 4 | nibble(*lo, tok)?,
+                    )),
+                    _ => Err(NoviMemError::Parse(format!(
+                        "invalid pattern byte '{}'",
+                        tok
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<PatternByte>>>()?;
+        if bytes.is_empty() {
+            return Err(NoviMemError::Parse("empty pattern".to_string()));
+        }
+        Ok(Pattern { bytes })
+    }
+
+    /// A pattern matching exactly `val`
+    pub fn from_bytes(val: &[u8]) -> Pattern {
+        Pattern {
+            bytes: val.iter().map(|b| PatternByte::Exact(*b)).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Whether `buf` starts with bytes matching the pattern
+    pub fn matches(&self, buf: &[u8]) -> bool {
+        buf.len() >= self.len() && self.bytes.iter().zip(buf).all(|(p, b)| p.matches(*b))
+    }
+
+    /// Compile the pattern into a byte regex
+    pub fn to_regex(&self) -> Result<Regex> {
+        let mut valstr = String::new();
+        self.bytes.iter().for_each(|b| match b {
+            PatternByte::Exact(x) => valstr.push_str(&format!("\\x{:02x}", x)),
+            PatternByte::High(x) => {
+                valstr.push_str(&format!("[\\x{:02x}-\\x{:02x}]", x << 4, x << 4 | 0xF))
+            }
+            PatternByte::Low(x) => {
+                valstr.push('[');
+                (0..16u8).for_each(|hi| valstr.push_str(&format!("\\x{:02x}", hi << 4 | x)));
+                valstr.push(']');
+            }
+            PatternByte::Any => valstr.push('.'),
+        });
+        let mut builder = RegexBuilder::new(&valstr);
+        builder
+            .unicode(false)
+            .dot_matches_new_line(true)
+            .case_insensitive(false);
+        builder
+            .build()
+            .map_err(|e| NoviMemError::Parse(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern() {
+        let p = Pattern::parse("48 8B 05 ?? ?? ?? ?? 89 4? ?0").unwrap();
+        assert_eq!(p.len(), 10);
+        let code = [
+            0x90, 0x48, 0x8B, 0x05, 1, 2, 3, 4, 0x89, 0x4C, 0x10, 0x48, 0x8B, 0x05, 1, 2, 3, 4,
+            0x89, 0x5C, 0x10,
+        ];
+        let re = p.to_regex().unwrap();
+        let found: Vec<usize> = re.find_iter(&code).map(|m| m.start()).collect();
+        assert_eq!(found, vec![1]);
+        assert!(p.matches(&code[1..]));
+        assert!(!p.matches(&code[11..]));
+        assert!(Pattern::parse("48 G1").is_err());
+        assert!(Pattern::parse("488B").is_err());
+        assert!(Pattern::parse("").is_err());
+    }
+}
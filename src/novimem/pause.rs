@@ -0,0 +1,112 @@
+use super::error::{NoviMemError, Result};
+use std::{
+    fs, io,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for every thread of the target to stop
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Keeps a process stopped with SIGSTOP while alive and sends SIGCONT when dropped,
+/// so the target resumes however the scan holding it ends
+pub struct PauseGuard {
+    pid: u32,
+    /// The process was already stopped, e.g. by a debugger, and should stay that way
+    was_stopped: bool,
+}
+
+/// The state letter of each thread of `pid` (`R`, `S`, `T`, ...)
+fn thread_states(pid: u32) -> Result<Vec<char>> {
+    let tasks = fs::read_dir(format!("/proc/{}/task", pid))
+        .map_err(|e| NoviMemError::from_mem_io(pid, 0, e))?;
+    let mut states = Vec::new();
+    for task in tasks {
+        // Threads may exit while we look at them
+        if let Ok(stat) = fs::read_to_string(task?.path().join("stat")) {
+            // The command name may contain anything, so look past its closing paren
+            if let Some(state) = stat
+                .rfind(')')
+                .and_then(|idx| stat[idx + 1..].trim_start().chars().next())
+            {
+                states.push(state);
+            }
+        }
+    }
+    Ok(states)
+}
+
+fn is_stopped(state: char) -> bool {
+    // T is stopped by a signal, t by a tracer; zombies and dead threads will not run either
+    matches!(state, 'T' | 't' | 'Z' | 'X')
+}
+
+fn signal(pid: u32, sig: libc::c_int) -> Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, sig) } == 0 {
+        Ok(())
+    } else {
+        Err(NoviMemError::from_mem_io(
+            pid,
+            0,
+            io::Error::last_os_error(),
+        ))
+    }
+}
+
+impl PauseGuard {
+    /// Stop every thread of `pid` and wait until they all have
+    pub fn new(pid: u32) -> Result<PauseGuard> {
+        let was_stopped = thread_states(pid)?.into_iter().all(is_stopped);
+        // SIGSTOP is delivered to the whole thread group
+        signal(pid, libc::SIGSTOP)?;
+        let guard = PauseGuard { pid, was_stopped };
+        let started = Instant::now();
+        while !thread_states(pid)?.into_iter().all(is_stopped) {
+            if started.elapsed() > STOP_TIMEOUT {
+                // Dropping the guard resumes the threads that did stop
+                return Err(NoviMemError::Io(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("process {} did not stop", pid),
+                )));
+            }
+            sleep(Duration::from_millis(1));
+        }
+        Ok(guard)
+    }
+}
+
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        if !self.was_stopped {
+            // Nothing to do if the process is gone
+            let _ = signal(self.pid, libc::SIGCONT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_pause_guard() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = child.id();
+        let scan = || -> Result<()> {
+            let _paused = PauseGuard::new(pid)?;
+            assert!(thread_states(pid)?.into_iter().all(is_stopped));
+            Err(NoviMemError::NoSnapshot)
+        };
+        assert!(scan().is_err());
+        // The failed scan still resumed the process
+        let started = Instant::now();
+        while thread_states(pid).unwrap().into_iter().any(is_stopped) {
+            assert!(started.elapsed() < STOP_TIMEOUT);
+            sleep(Duration::from_millis(1));
+        }
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(PauseGuard::new(pid).is_err());
+    }
+}
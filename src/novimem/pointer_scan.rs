@@ -0,0 +1,582 @@
+use super::{
+    error::{NoviMemError, Result},
+    MemRegion, NoviMem,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, mem::size_of, path::Path};
+
+/// A path to a value: read a pointer at `module`'s base + `offset`, add the first offset,
+/// read a pointer there, ... and the last offset lands on the value
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PointerChain {
+    /// Path of the module the chain starts in, as named in /proc/<pid>/maps
+    pub module: String,
+    /// Offset of the first pointer from the module's load address
+    pub offset: u64,
+    pub offsets: Vec<u64>,
+}
+
+impl fmt::Display for PointerChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.module.rsplit('/').next().unwrap_or(&self.module);
+        write!(f, "{}+{:X}", name, self.offset)?;
+        self.offsets
+            .iter()
+            .try_for_each(|o| write!(f, " -> +{:X}", o))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PointerScanOptions {
+    /// Most pointers to follow from the static base to the target
+    pub max_depth: usize,
+    /// Largest offset added to any pointer along a chain
+    pub max_offset: u64,
+    /// Stop once this many chains were found
+    pub max_results: usize,
+    /// Stop after following this many pointers, whether or not anything was found
+    pub max_visited: usize,
+    /// Alignment of the pointers themselves
+    pub alignment: usize,
+}
+
+impl Default for PointerScanOptions {
+    fn default() -> Self {
+        PointerScanOptions {
+            max_depth: 4,
+            max_offset: 0x1000,
+            max_results: 10_000,
+            max_visited: 10_000_000,
+            alignment: size_of::<u64>(),
+        }
+    }
+}
+
+/// A loaded module's image, where static pointer bases live
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Module {
+    pub name: String,
+    pub start_addr: u64,
+    pub end_addr: u64,
+}
+
+/// Modules are the file-backed regions; a module spans all regions mapped from its file,
+/// along with the anonymous mapping right after them that holds its `.bss`
+pub fn modules(regions: &[MemRegion]) -> Vec<Module> {
+    let mut modules: Vec<Module> = Vec::new();
+    // The module the previous region belongs to
+    let mut prev: Option<(usize, u64)> = None;
+    for r in regions {
+        let idx = if r.name.starts_with('/') {
+            match modules.iter().position(|m| m.name == r.name) {
+                Some(idx) => Some(idx),
+                None => {
+                    modules.push(Module {
+                        name: r.name.clone(),
+                        start_addr: r.start_addr,
+                        end_addr: r.end_addr,
+                    });
+                    Some(modules.len() - 1)
+                }
+            }
+        } else {
+            prev.filter(|(_, end)| r.pathname.is_empty() && *end == r.start_addr)
+                .map(|(idx, _)| idx)
+        };
+        if let Some(idx) = idx {
+            let m = &mut modules[idx];
+            m.start_addr = m.start_addr.min(r.start_addr);
+            m.end_addr = m.end_addr.max(r.end_addr);
+        }
+        // Only the first anonymous mapping after the file is taken to be its .bss
+        prev = idx
+            .filter(|_| !r.pathname.is_empty())
+            .map(|idx| (idx, r.end_addr));
+    }
+    modules
+}
+
+/// Find a module by its path or by its file name
+pub fn find_module<'a>(modules: &'a [Module], name: &str) -> Option<&'a Module> {
+    modules
+        .iter()
+        .find(|m| m.name == name || m.name.rsplit('/').next() == Some(name))
+}
+
+/// Every aligned pointer-sized value in the scanned regions that points into a mapped region,
+/// kept as (value, address) sorted by value so pointers to an area can be looked up
+pub struct PointerList {
+    entries: Vec<(u64, u64)>,
+}
+
+impl PointerList {
+    pub fn new(mut entries: Vec<(u64, u64)>) -> PointerList {
+        entries.sort_unstable();
+        PointerList { entries }
+    }
+
+    /// (value, address) of the pointers whose value is within `lo..=hi`
+    pub fn pointing_into(&self, lo: u64, hi: u64) -> &[(u64, u64)] {
+        let start = self.entries.partition_point(|(v, _)| *v < lo);
+        let end = self.entries.partition_point(|(v, _)| *v <= hi);
+        &self.entries[start..end]
+    }
+
+    pub fn entries(&self) -> &[(u64, u64)] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// A region a pointer map's addresses are relative to, with where it was mapped in that run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapRegion {
+    pub name: String,
+    pub start_addr: u64,
+    pub end_addr: u64,
+}
+
+/// First line of a saved pointer map
+const MAP_MAGIC: &[u8] = b"NOVIMEM PTRMAP 1\n";
+/// Size of a saved pointer: region index (u32), offset (u64) and value (u64), little-endian
+const MAP_RECORD: usize = 20;
+
+/// Every pointer found in one run of a process, kept so chains can be checked against
+/// several runs without the processes. Each pointer's address is kept as an offset into
+/// one of `regions`, and `modules` records where each module was loaded, so a chain's
+/// module-relative base and the pointers it follows are found however that run was laid out.
+///
+/// Saved maps are a line of magic, the rest of the map as one line of JSON, and then the
+/// pointers as fixed-size binary records, since a map may hold millions of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointerMap {
+    pub pname: String,
+    pub modules: Vec<Module>,
+    /// Regions the pointers were found in, by ascending address
+    pub regions: Vec<MapRegion>,
+    /// (region index, offset into the region, value) of each pointer, by ascending address.
+    /// Saved as binary records after the rest of the map.
+    #[serde(skip)]
+    pub pointers: Vec<(u32, u64, u64)>,
+}
+
+impl PointerMap {
+    pub fn load(path: &Path) -> Result<PointerMap> {
+        let data = fs::read(path)?;
+        let bad = |what: &str| NoviMemError::Parse(format!("{}: {}", path.display(), what));
+        let rest = data
+            .strip_prefix(MAP_MAGIC)
+            .ok_or_else(|| bad("not a pointer map"))?;
+        let header_len = rest
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| bad("truncated header"))?;
+        let mut map: PointerMap = serde_json::from_slice(&rest[..header_len])?;
+        let records = &rest[header_len + 1..];
+        if !records.len().is_multiple_of(MAP_RECORD) {
+            return Err(bad("truncated pointer records"));
+        }
+        map.pointers = records
+            .chunks_exact(MAP_RECORD)
+            .map(|rec| {
+                let mut region = [0u8; 4];
+                let mut offset = [0u8; 8];
+                let mut value = [0u8; 8];
+                region.copy_from_slice(&rec[..4]);
+                offset.copy_from_slice(&rec[4..12]);
+                value.copy_from_slice(&rec[12..]);
+                (
+                    u32::from_le_bytes(region),
+                    u64::from_le_bytes(offset),
+                    u64::from_le_bytes(value),
+                )
+            })
+            .collect();
+        if map
+            .pointers
+            .iter()
+            .any(|(region, _, _)| *region as usize >= map.regions.len())
+        {
+            return Err(bad("pointer in an unknown region"));
+        }
+        Ok(map)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut data = MAP_MAGIC.to_vec();
+        serde_json::to_writer(&mut data, self)?;
+        data.push(b'\n');
+        data.reserve(self.pointers.len() * MAP_RECORD);
+        for (region, offset, value) in &self.pointers {
+            data.extend(&region.to_le_bytes());
+            data.extend(&offset.to_le_bytes());
+            data.extend(&value.to_le_bytes());
+        }
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// The pointer stored at `addr`, if there was one
+    pub fn read_pointer(&self, addr: u64) -> Option<u64> {
+        let idx = self
+            .regions
+            .partition_point(|r| r.start_addr <= addr)
+            .checked_sub(1)?;
+        let region = &self.regions[idx];
+        if addr >= region.end_addr {
+            return None;
+        }
+        let key = (idx as u32, addr - region.start_addr);
+        self.pointers
+            .binary_search_by_key(&key, |(r, o, _)| (*r, *o))
+            .ok()
+            .map(|i| self.pointers[i].2)
+    }
+
+    /// Follow `chain` through the map and return the address it ends at
+    pub fn resolve(&self, chain: &PointerChain) -> Option<u64> {
+        let module = find_module(&self.modules, &chain.module)?;
+        chain
+            .offsets
+            .iter()
+            .try_fold(module.start_addr + chain.offset, |addr, offset| {
+                self.read_pointer(addr).map(|p| p.wrapping_add(*offset))
+            })
+    }
+
+    /// The pointers by value, for scanning the map for chains
+    pub fn pointer_list(&self) -> PointerList {
+        PointerList::new(
+            self.pointers
+                .iter()
+                .map(|(r, o, v)| (*v, self.regions[*r as usize].start_addr + o))
+                .collect(),
+        )
+    }
+}
+
+/// Walk backwards from `target` through `pointers`, collecting chains whose base is in a module
+pub fn find_chains(
+    pointers: &PointerList,
+    modules: &[Module],
+    target: u64,
+    opts: &PointerScanOptions,
+) -> Vec<PointerChain> {
+    let mut chains = Vec::new();
+    let mut suffix = Vec::new();
+    let mut visited = 0;
+    walk(
+        pointers,
+        modules,
+        target,
+        opts,
+        &mut suffix,
+        &mut chains,
+        &mut visited,
+    );
+    chains.sort_by(|a, b| {
+        (a.offsets.len(), &a.module, a.offset).cmp(&(b.offsets.len(), &b.module, b.offset))
+    });
+    chains.dedup();
+    chains
+}
+
+fn walk(
+    pointers: &PointerList,
+    modules: &[Module],
+    target: u64,
+    opts: &PointerScanOptions,
+    suffix: &mut Vec<u64>,
+    chains: &mut Vec<PointerChain>,
+    visited: &mut usize,
+) {
+    if suffix.len() >= opts.max_depth {
+        return;
+    }
+    for (value, addr) in pointers.pointing_into(target.saturating_sub(opts.max_offset), target) {
+        if chains.len() >= opts.max_results || *visited >= opts.max_visited {
+            return;
+        }
+        *visited += 1;
+        suffix.insert(0, target - value);
+        if let Some(m) = modules
+            .iter()
+            .find(|m| m.start_addr <= *addr && m.end_addr > *addr)
+        {
+            chains.push(PointerChain {
+                module: m.name.clone(),
+                offset: addr - m.start_addr,
+                offsets: suffix.clone(),
+            });
+        } else {
+            walk(pointers, modules, *addr, opts, suffix, chains, visited);
+        }
+        suffix.remove(0);
+    }
+}
+
+impl NoviMem {
+    /// Collect every aligned pointer in the scanned regions that points into a mapped region
+    pub fn pointer_map(&mut self, alignment: usize) -> Result<PointerMap> {
+        let size = size_of::<u64>();
+        self.refresh_regions()?;
+        let _paused = self.pause_target()?;
+        let found = self.scan_chunks(size - 1, |chunk| {
+            super::aligned_offsets(chunk.addr, chunk.data.len(), size, alignment)
+                .filter(|i| chunk.owns(*i))
+                .filter_map(|i| {
+                    let mut arr = [0u8; 8];
+                    arr.copy_from_slice(&chunk.data[i..i + size]);
+                    let value = u64::from_le_bytes(arr);
+                    self.is_mapped(value).then(|| {
+                        let offset = chunk.addr + i as u64 - chunk.region_start;
+                        (chunk.region_start, offset, value)
+                    })
+                })
+                .collect::<Vec<(u64, u64, u64)>>()
+        })?;
+        let mut found: Vec<(u64, u64, u64)> = found.into_iter().flatten().collect();
+        found.sort_unstable();
+        let mut regions: Vec<MapRegion> = Vec::new();
+        let mut pointers = Vec::with_capacity(found.len());
+        for (region_start, offset, value) in found {
+            if regions.last().map(|r| r.start_addr) != Some(region_start) {
+                let r = self
+                    .regions
+                    .iter()
+                    .find(|r| r.start_addr == region_start)
+                    .ok_or(NoviMemError::UnmappedAddress(region_start))?;
+                regions.push(MapRegion {
+                    name: r.name.clone(),
+                    start_addr: r.start_addr,
+                    end_addr: r.end_addr,
+                });
+            }
+            pointers.push(((regions.len() - 1) as u32, offset, value));
+        }
+        Ok(PointerMap {
+            pname: self.pname.clone(),
+            modules: modules(&self.regions),
+            regions,
+            pointers,
+        })
+    }
+
+    /// Find pointer chains from static module addresses to `target`
+    pub fn pointer_scan(
+        &mut self,
+        target: u64,
+        opts: &PointerScanOptions,
+    ) -> Result<&[PointerChain]> {
+        let map = self.pointer_map(opts.alignment)?;
+        self.pointer_chains = find_chains(&map.pointer_list(), &map.modules, target, opts);
+        Ok(&self.pointer_chains)
+    }
+
+    /// Follow `chain` in the current process and return the address it ends at
+    pub fn resolve_chain(&self, chain: &PointerChain) -> Result<u64> {
+        let mut addr = self.module_base(&chain.module)? + chain.offset;
+        for offset in &chain.offsets {
+            let mut arr = [0u8; 8];
+            arr.copy_from_slice(&self.getval(addr, size_of::<u64>())?);
+            addr = u64::from_le_bytes(arr).wrapping_add(*offset);
+        }
+        Ok(addr)
+    }
+
+    /// Keep the chains that still resolve, and that land on `target` if given,
+    /// e.g. after the target was restarted and the value found again
+    pub fn rescan_pointer_chains(&mut self, target: Option<u64>) -> usize {
+        let chains = std::mem::take(&mut self.pointer_chains);
+        self.pointer_chains = chains
+            .into_iter()
+            .filter(|c| match (self.resolve_chain(c), target) {
+                (Ok(addr), Some(t)) => addr == t,
+                (Ok(addr), None) => self.is_mapped(addr),
+                (Err(_), _) => false,
+            })
+            .collect();
+        self.pointer_chains.len()
+    }
+
+    /// Keep the chains that lead to the given target address in every one of `maps`
+    pub fn check_pointer_chains(&mut self, maps: &[(PointerMap, u64)]) -> usize {
+        self.pointer_chains.retain(|c| {
+            maps.iter()
+                .all(|(map, target)| map.resolve(c) == Some(*target))
+        });
+        self.pointer_chains.len()
+    }
+
+    pub fn pointer_chains(&self) -> &[PointerChain] {
+        &self.pointer_chains
+    }
+
+    pub fn save_pointer_chains(&self) -> Result<()> {
+        let json = serde_json::to_string(&self.pointer_chains)?;
+        fs::write(format!("./{}.pointers", self.pname), json)?;
+        Ok(())
+    }
+
+    pub fn load_pointer_chains(&mut self) -> Result<usize> {
+        let json = fs::read(format!("./{}.pointers", self.pname))?;
+        self.pointer_chains = serde_json::from_slice(&json)?;
+        Ok(self.pointer_chains.len())
+    }
+
+    /// Where the module with this path or file name is loaded
+    pub fn module_base(&self, name: &str) -> Result<u64> {
+        find_module(&modules(&self.regions), name)
+            .map(|m| m.start_addr)
+            .ok_or_else(|| NoviMemError::Parse(format!("module '{}' not loaded", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::novimem::backend::mock::MockBackend;
+
+    /// game+0x10 -> heap+0x20 (+0x8) -> heap+0x100 (+0x4) -> value
+    fn pointer_mem(heap_base: u64) -> NoviMem {
+        let mut backend = MockBackend::new();
+        let mut data = vec![0u8; 0x40];
+        data[0x10..0x18].copy_from_slice(&(heap_base + 0x18).to_le_bytes());
+        backend.add_region("/usr/bin/game", 0x40_0000, data);
+        let mut heap = vec![0u8; 0x200];
+        heap[0x20..0x28].copy_from_slice(&(heap_base + 0xFC).to_le_bytes());
+        heap[0x100..0x104].copy_from_slice(&1337u32.to_le_bytes());
+        backend.add_region("[heap]", heap_base, heap);
+        NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap()
+    }
+
+    #[test]
+    fn test_pointer_scan() {
+        let mut m = pointer_mem(0x10_0000);
+        let opts = PointerScanOptions {
+            max_depth: 3,
+            max_offset: 0x10,
+            ..PointerScanOptions::default()
+        };
+        let chains = m.pointer_scan(0x10_0100, &opts).unwrap().to_vec();
+        assert_eq!(
+            chains,
+            vec![PointerChain {
+                module: "/usr/bin/game".to_string(),
+                offset: 0x10,
+                offsets: vec![0x8, 0x4],
+            }]
+        );
+        assert_eq!(chains[0].to_string(), "game+10 -> +8 -> +4");
+        assert_eq!(m.resolve_chain(&chains[0]).unwrap(), 0x10_0100);
+
+        // Too shallow to reach the module
+        let shallow = PointerScanOptions {
+            max_depth: 1,
+            ..opts.clone()
+        };
+        assert!(m.pointer_scan(0x10_0100, &shallow).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_bss_base() {
+        // game's .bss follows its file mappings; the anonymous region after it does not
+        let mut backend = MockBackend::new();
+        backend.add_region("/usr/bin/game", 0x40_0000, vec![0u8; 0x1000]);
+        let mut bss = vec![0u8; 0x1000];
+        bss[0x10..0x18].copy_from_slice(&0x10_0000u64.to_le_bytes());
+        backend.add_region("", 0x40_1000, bss);
+        backend.add_region("", 0x40_2000, vec![0u8; 0x1000]);
+        let mut anon = vec![0u8; 0x1000];
+        anon[0x20..0x28].copy_from_slice(&0x10_0000u64.to_le_bytes());
+        backend.add_region("", 0x50_0000, anon);
+        backend.add_region("[heap]", 0x10_0000, vec![0u8; 0x100]);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+
+        let game = &modules(m.regions())[0];
+        assert_eq!((game.start_addr, game.end_addr), (0x40_0000, 0x40_2000));
+        let chains = m
+            .pointer_scan(0x10_0004, &PointerScanOptions::default())
+            .unwrap();
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].to_string(), "game+1010 -> +4");
+
+        // Nothing more is searched once enough pointers were followed
+        let capped = PointerScanOptions {
+            max_visited: 1,
+            ..PointerScanOptions::default()
+        };
+        assert_eq!(m.pointer_scan(0x10_0004, &capped).unwrap().len(), 1);
+        let capped = PointerScanOptions {
+            max_visited: 0,
+            ..capped
+        };
+        assert!(m.pointer_scan(0x10_0004, &capped).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pointer_maps() {
+        let dir = std::env::temp_dir().join(format!("novimem-ptrmap-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut maps = Vec::new();
+        for (idx, heap_base) in [0x10_0000, 0x90_0000].iter().enumerate() {
+            let path = dir.join(format!("run{}.ptrmap", idx));
+            pointer_mem(*heap_base)
+                .pointer_map(8)
+                .unwrap()
+                .save(&path)
+                .unwrap();
+            maps.push((PointerMap::load(&path).unwrap(), heap_base + 0x100));
+        }
+        // Addresses are kept relative to their region
+        assert_eq!(
+            maps[1].0.pointers,
+            vec![(0, 0x10, 0x90_0018), (1, 0x20, 0x90_00FC)]
+        );
+        assert_eq!(maps[1].0.regions[1].start_addr, 0x90_0000);
+        assert_eq!(maps[1].0.read_pointer(0x40_0010), Some(0x90_0018));
+        assert_eq!(maps[1].0.read_pointer(0x40_0018), None);
+
+        let mut m = pointer_mem(0x10_0000);
+        m.pointer_scan(0x10_0100, &PointerScanOptions::default())
+            .unwrap();
+        assert_eq!(m.check_pointer_chains(&maps), 2);
+        // A run where the value lived elsewhere rules every chain out
+        maps[0].1 = 0x10_0200;
+        assert_eq!(m.check_pointer_chains(&maps), 0);
+
+        // Cut off partway through the pointers
+        let path = dir.join("run0.ptrmap");
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert!(matches!(
+            PointerMap::load(&path),
+            Err(NoviMemError::Parse(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rescan_after_restart() {
+        let mut m = pointer_mem(0x10_0000);
+        m.pointer_scan(0x10_0100, &PointerScanOptions::default())
+            .unwrap();
+        let chains = m.pointer_chains().to_vec();
+        // game+10 -> +E8 reaches the value directly, as does game+10 -> +8 -> +4
+        assert_eq!(chains.len(), 2);
+        assert_eq!(chains[0].offsets, vec![0xE8]);
+
+        // The heap moved, the chains still lead to the value
+        let mut restarted = pointer_mem(0x90_0000);
+        restarted.pointer_chains = chains;
+        assert_eq!(restarted.rescan_pointer_chains(Some(0x90_0100)), 2);
+        assert_eq!(restarted.rescan_pointer_chains(Some(0x10_0100)), 0);
+    }
+}
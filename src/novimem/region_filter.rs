@@ -0,0 +1,257 @@
+use super::{
+    error::{NoviMemError, Result},
+    MemRegion,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What a region must be mapped from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backing {
+    Any,
+    /// Mapped from a file, like a library's data
+    File,
+    /// Not backed by a file: the heap, the stack and anonymous mappings
+    Anonymous,
+}
+
+/// Which regions searches, snapshots and pointer scans look at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegionFilter {
+    /// Permissions as in /proc/<pid>/maps, e.g. `rw??`: a letter must be set,
+    /// `-` must be unset and `?` may be either
+    pub perms: String,
+    /// Also take readable executable regions that fail `perms`, to find code
+    pub exec: bool,
+    /// Name patterns a region must match one of, if any are given
+    pub include: Vec<String>,
+    /// Name patterns of regions to skip. Patterns are globs (`*`, `?`) or, prefixed
+    /// with `re:`, regexes, matched against the full name and the file name.
+    pub exclude: Vec<String>,
+    pub min_size: Option<usize>,
+    pub max_size: Option<usize>,
+    pub backing: Backing,
+}
+
+impl Default for RegionFilter {
+    /// Writeable data outside the stack
+    fn default() -> Self {
+        RegionFilter {
+            perms: "rw??".to_string(),
+            exec: false,
+            include: Vec::new(),
+            exclude: vec!["[stack]".to_string()],
+            min_size: None,
+            max_size: None,
+            backing: Backing::Any,
+        }
+    }
+}
+
+impl fmt::Display for RegionFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "perms {}", self.perms)?;
+        if self.exec {
+            write!(f, ", executable")?;
+        }
+        if !self.include.is_empty() {
+            write!(f, ", include {}", self.include.join(" "))?;
+        }
+        if !self.exclude.is_empty() {
+            write!(f, ", exclude {}", self.exclude.join(" "))?;
+        }
+        match (self.min_size, self.max_size) {
+            (None, None) => {}
+            (min, max) => write!(
+                f,
+                ", size {}..{}",
+                min.map(|s| s.to_string()).unwrap_or_default(),
+                max.map(|s| s.to_string()).unwrap_or_default()
+            )?,
+        }
+        match self.backing {
+            Backing::Any => Ok(()),
+            Backing::File => write!(f, ", file-backed"),
+            Backing::Anonymous => write!(f, ", anonymous"),
+        }
+    }
+}
+
+impl Backing {
+    pub fn parse(name: &str) -> Option<Backing> {
+        match name {
+            "any" => Some(Backing::Any),
+            "file" => Some(Backing::File),
+            "anon" | "anonymous" => Some(Backing::Anonymous),
+            _ => None,
+        }
+    }
+}
+
+fn name_pattern(pattern: &str) -> Result<Regex> {
+    let re = match pattern.strip_prefix("re:") {
+        Some(re) => re.to_string(),
+        None => {
+            let glob: String = pattern
+                .chars()
+                .map(|c| match c {
+                    '*' => ".*".to_string(),
+                    '?' => ".".to_string(),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect();
+            format!("^{}$", glob)
+        }
+    };
+    Regex::new(&re).map_err(|e| NoviMemError::Parse(format!("pattern '{}': {}", pattern, e)))
+}
+
+/// A `RegionFilter` with its patterns compiled
+#[derive(Debug, Clone)]
+pub struct RegionMatcher {
+    filter: RegionFilter,
+    /// For r, w, x and p/s: whether the permission must be set, unset, or either
+    perms: [Option<bool>; 4],
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl RegionMatcher {
+    pub fn new(filter: RegionFilter) -> Result<RegionMatcher> {
+        let err = || {
+            NoviMemError::Parse(format!(
+                "permissions '{}' should look like 'rw-p', with '?' for either",
+                filter.perms
+            ))
+        };
+        let chars: Vec<char> = filter.perms.chars().collect();
+        if chars.len() != 4 {
+            return Err(err());
+        }
+        let mut perms = [None; 4];
+        for (i, (c, set)) in chars.iter().zip(['r', 'w', 'x', 'p']).enumerate() {
+            perms[i] = match c {
+                '?' => None,
+                '-' => Some(false),
+                // For the sharing flag, `s` asks for shared memory
+                's' if i == 3 => Some(false),
+                c if *c == set => Some(true),
+                _ => return Err(err()),
+            };
+        }
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| name_pattern(p))
+                .collect::<Result<Vec<Regex>>>()
+        };
+        Ok(RegionMatcher {
+            include: compile(&filter.include)?,
+            exclude: compile(&filter.exclude)?,
+            perms,
+            filter,
+        })
+    }
+
+    pub fn filter(&self) -> &RegionFilter {
+        &self.filter
+    }
+
+    pub fn set_exec(&mut self, exec: bool) {
+        self.filter.exec = exec;
+    }
+
+    pub fn matches(&self, r: &MemRegion) -> bool {
+        let flags = [r.readable, r.writeable, r.execable, r.private];
+        let perms_ok = self
+            .perms
+            .iter()
+            .zip(flags.iter())
+            .all(|(want, has)| want.is_none_or(|w| w == *has));
+        let name_matches = |re: &Regex| {
+            re.is_match(&r.name) || r.name.rsplit('/').next().is_some_and(|n| re.is_match(n))
+        };
+        let file_backed = r.pathname.starts_with('/');
+        (perms_ok || (self.filter.exec && r.readable && r.execable))
+            && (self.include.is_empty() || self.include.iter().any(name_matches))
+            && !self.exclude.iter().any(name_matches)
+            && self.filter.min_size.is_none_or(|min| r.size >= min)
+            && self.filter.max_size.is_none_or(|max| r.size <= max)
+            && match self.filter.backing {
+                Backing::Any => true,
+                Backing::File => file_backed,
+                Backing::Anonymous => !file_backed,
+            }
+    }
+}
+
+impl Default for RegionMatcher {
+    fn default() -> Self {
+        RegionMatcher::new(RegionFilter::default()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(name: &str, perms: &str, size: usize) -> MemRegion {
+        let p: Vec<char> = perms.chars().collect();
+        MemRegion {
+            start_addr: 0x1000,
+            end_addr: 0x1000 + size as u64,
+            size,
+            readable: p[0] == 'r',
+            writeable: p[1] == 'w',
+            execable: p[2] == 'x',
+            private: p[3] == 'p',
+            shared: p[3] == 's',
+            name: name.to_string(),
+            file_offset: 0,
+            pathname: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_region_filter() {
+        let heap = region("[heap]", "rw-p", 0x1000);
+        let stack = region("[stack]", "rw-p", 0x1000);
+        let code = region("/usr/lib/libc.so.6", "r-xp", 0x10000);
+        let data = region("/usr/lib/libc.so.6", "rw-p", 0x100);
+        let shared = region("/dev/shm/game", "rw-s", 0x100);
+
+        let default = RegionMatcher::default();
+        assert!(default.matches(&heap) && default.matches(&data) && default.matches(&shared));
+        assert!(!default.matches(&stack) && !default.matches(&code));
+
+        let mut filter = RegionFilter {
+            perms: "r???".to_string(),
+            include: vec!["libc*".to_string()],
+            ..RegionFilter::default()
+        };
+        let libc = RegionMatcher::new(filter.clone()).unwrap();
+        assert!(libc.matches(&code) && libc.matches(&data) && !libc.matches(&heap));
+
+        filter.include = vec!["re:^\\[(heap|stack)\\]$".to_string()];
+        filter.exclude.clear();
+        filter.max_size = Some(0x1000);
+        let anon = RegionMatcher::new(filter.clone()).unwrap();
+        assert!(anon.matches(&heap) && anon.matches(&stack) && !anon.matches(&code));
+
+        let shared_only = RegionFilter {
+            perms: "rw?s".to_string(),
+            backing: Backing::File,
+            ..RegionFilter::default()
+        };
+        let m = RegionMatcher::new(shared_only).unwrap();
+        assert!(m.matches(&shared) && !m.matches(&data) && !m.matches(&heap));
+
+        filter.perms = "rwz".to_string();
+        assert!(RegionMatcher::new(filter.clone()).is_err());
+        filter.perms = "rw??".to_string();
+        filter.include = vec!["re:(".to_string()];
+        assert!(RegionMatcher::new(filter).is_err());
+    }
+}
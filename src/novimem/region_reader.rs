@@ -0,0 +1,230 @@
+use super::{error::Result, NoviMem};
+use rayon::prelude::*;
+
+/// Bytes of a region read at once
+pub const CHUNK_SIZE: usize = 1 << 20;
+/// Granularity at which unreadable memory is skipped
+pub const PAGE_SIZE: u64 = 4096;
+
+/// A run of readable bytes of a scanned region. Chunks are scanned independently, so
+/// each reads a little past the bytes it owns to see matches starting near its end whole.
+pub struct Chunk {
+    pub addr: u64,
+    pub data: Vec<u8>,
+    /// Leading bytes of `data` this chunk reports matches for
+    pub owned: usize,
+    /// Start of the region the chunk is in
+    pub region_start: u64,
+}
+
+impl Chunk {
+    /// Whether a match at `offset` belongs to this chunk rather than the next one
+    pub fn owns(&self, offset: usize) -> bool {
+        offset < self.owned
+    }
+}
+
+/// A piece of a scanned region of at most the chunk size
+struct Segment {
+    start: u64,
+    end: u64,
+    region_start: u64,
+    region_end: u64,
+}
+
+impl NoviMem {
+    /// Read the scanned regions a chunk at a time on the rayon thread pool and collect
+    /// what `f` makes of each chunk, in address order. Chunks read `overlap` bytes past
+    /// what they own. Pages that cannot be read are skipped rather than failing the scan.
+    pub(crate) fn scan_chunks<R, F>(&self, overlap: usize, f: F) -> Result<Vec<R>>
+    where
+        R: Send,
+        F: Fn(Chunk) -> R + Sync,
+    {
+        let chunk_size = self.chunk_size as u64;
+        let segments: Vec<Segment> = self
+            .scan_regions()
+            .flat_map(|r| {
+                (r.start_addr..r.end_addr)
+                    .step_by(self.chunk_size)
+                    .map(move |start| Segment {
+                        start,
+                        end: (start + chunk_size).min(r.end_addr),
+                        region_start: r.start_addr,
+                        region_end: r.end_addr,
+                    })
+            })
+            .collect();
+        let found = segments
+            .par_iter()
+            .map(|seg| {
+                let len = (seg.end + overlap as u64).min(seg.region_end) - seg.start;
+                let mut found = Vec::new();
+                for (addr, data) in self.read_runs(seg.start, len as usize)? {
+                    let owned = seg.end.saturating_sub(addr).min(data.len() as u64) as usize;
+                    if owned > 0 {
+                        found.push(f(Chunk {
+                            addr,
+                            data,
+                            owned,
+                            region_start: seg.region_start,
+                        }));
+                    }
+                }
+                Ok(found)
+            })
+            .collect::<Result<Vec<Vec<R>>>>()?;
+        Ok(found.into_iter().flatten().collect())
+    }
+
+    /// Read `len` bytes at `addr` as (address, bytes) runs, leaving out every page
+    /// that fails to read. Errors other than unreadable memory are returned.
+    pub(crate) fn read_runs(&self, addr: u64, len: usize) -> Result<Vec<(u64, Vec<u8>)>> {
+        let mut buf = vec![0u8; len];
+        let mut runs = Vec::new();
+        let mut run_start = 0;
+        let mut pos = 0;
+        while pos < len {
+            match self.backend.read(addr + pos as u64, &mut buf[pos..]) {
+                Ok(n) if n > 0 => {
+                    pos += n;
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) if e.is_recoverable() => {}
+                Err(e) => return Err(e),
+            }
+            // Skip the page that failed and carry on after it
+            if pos > run_start {
+                runs.push((addr + run_start as u64, buf[run_start..pos].to_vec()));
+            }
+            let next_page = ((addr + pos as u64) / PAGE_SIZE + 1) * PAGE_SIZE;
+            pos = ((next_page - addr) as usize).min(len);
+            run_start = pos;
+        }
+        if pos > run_start {
+            buf.truncate(pos);
+            runs.push((addr + run_start as u64, buf.split_off(run_start)));
+        }
+        Ok(runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::novimem::{
+        backend::mock::MockBackend, pattern::Pattern, value::DataType, SearchType,
+    };
+
+    #[test]
+    fn test_chunked_reads() {
+        let mut backend = MockBackend::new();
+        let mut heap = vec![0u8; 5 * PAGE_SIZE as usize];
+        heap[0xFFE..0x1002].copy_from_slice(&1234u32.to_le_bytes());
+        heap[0x1FFD..0x2003].copy_from_slice(b"needle");
+        heap[0x3010..0x3014].copy_from_slice(&1234u32.to_le_bytes());
+        heap[0x4000..0x4004].copy_from_slice(&1234u32.to_le_bytes());
+        backend.add_region("[heap]", 0x10000, heap);
+        backend.set_unreadable(0x13000);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+        m.set_chunk_size(1);
+        assert_eq!(m.chunk_size(), PAGE_SIZE as usize);
+
+        let runs = m.read_runs(0x10000, 5 * PAGE_SIZE as usize).unwrap();
+        let runs: Vec<(u64, usize)> = runs.iter().map(|(a, d)| (*a, d.len())).collect();
+        assert_eq!(runs, vec![(0x10000, 0x3000), (0x14000, 0x1000)]);
+
+        // Matches across chunk boundaries are found once, the unreadable page is skipped
+        let pattern = Pattern::from_bytes(&1234u32.to_le_bytes());
+        assert_eq!(m.search_pattern(&pattern, 1).unwrap(), 2);
+        assert_eq!(m.results(), &vec![0x10FFE, 0x14000]);
+        m.clear_results();
+        assert_eq!(m.search_pattern(&pattern, 2).unwrap(), 2);
+        m.clear_results();
+        assert_eq!(m.search_regex("needle", false).unwrap(), 1);
+        assert_eq!(m.results(), &vec![0x11FFD]);
+
+        m.init_snapshots(DataType::U32, 4).unwrap();
+        assert_eq!(m.snapshots.runs().len(), 2);
+        m.setval(0x10000, &1u32.to_le_bytes()).unwrap();
+        m.setval(0x14000, &1u32.to_le_bytes()).unwrap();
+        assert_eq!(m.take_snapshots(Some(SearchType::Changed)).unwrap(), 2);
+        assert_eq!(m.results(), &vec![0x10000, 0x14000]);
+    }
+
+    #[test]
+    fn test_parallel_scan_order() {
+        let mut backend = MockBackend::new();
+        let mut expected = Vec::new();
+        for (i, start) in [0x10000u64, 0x30000, 0x50000].iter().enumerate() {
+            let mut data = vec![0u8; 8 * PAGE_SIZE as usize];
+            for off in (0x7FE..data.len() - 4).step_by(0x800 + i) {
+                data[off..off + 4].copy_from_slice(&1234u32.to_le_bytes());
+                expected.push(start + off as u64);
+            }
+            backend.add_region("[heap]", *start, data);
+        }
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+        m.set_chunk_size(PAGE_SIZE as usize);
+        let pattern = Pattern::from_bytes(&1234u32.to_le_bytes());
+        for _ in 0..4 {
+            m.clear_results();
+            m.search_pattern(&pattern, 1).unwrap();
+            assert_eq!(m.results(), &expected);
+        }
+
+        // Overlapping matches found by neighbouring chunks are reported once
+        let mut backend = MockBackend::new();
+        backend.add_region("[heap]", 0x10000, vec![b'a'; 2 * PAGE_SIZE as usize]);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+        m.set_chunk_size(PAGE_SIZE as usize);
+        assert_eq!(m.search_regex("aaa", false).unwrap(), 0x2000 / 3);
+        assert!(m.results().to_vec().windows(2).all(|w| w[1] - w[0] >= 3));
+    }
+
+    /// Prints how long scanning this process through /proc/self/mem takes on one thread
+    /// and on the whole pool. Timings depend on the machine, so only the hits are checked.
+    /// Run with `--ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_parallel_scan() {
+        use std::time::{Duration, Instant};
+
+        let mut haystack = vec![0u8; 256 << 20];
+        for off in (0x123..haystack.len()).step_by(1 << 20) {
+            haystack[off..off + 8].copy_from_slice(b"NOVIMARK");
+        }
+        let haystack = std::hint::black_box(haystack);
+        let mut m = NoviMem::new(std::process::id(), String::from("novimem")).unwrap();
+        let pattern = Pattern::from_bytes(b"NOVIMARK");
+        let mut scan = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let mut best = Duration::MAX;
+            for _ in 0..3 {
+                m.clear_results();
+                let start = Instant::now();
+                pool.install(|| m.search_pattern(&pattern, 1)).unwrap();
+                best = best.min(start.elapsed());
+            }
+            (best, m.results().len())
+        };
+        let threads = rayon::current_num_threads();
+        let (serial, serial_found) = scan(1);
+        let (parallel, parallel_found) = scan(threads);
+        println!(
+            "{} found, 1 thread: {:?}, {} threads: {:?} ({:.1}x)",
+            serial_found,
+            serial,
+            threads,
+            parallel,
+            serial.as_secs_f64() / parallel.as_secs_f64()
+        );
+        // The pattern itself lives in this process too, so only a lower bound is known
+        assert!(serial_found >= 256 && parallel_found >= 256);
+        drop(haystack);
+    }
+}
@@ -0,0 +1,343 @@
+use super::error::Result;
+use rayon::prelude::*;
+use std::fmt;
+
+/// Result sets at most this large are kept as explicit addresses
+pub const LIST_LIMIT: usize = 1 << 20;
+
+/// One bit per `align`-spaced slot of an address range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    start: u64,
+    align: u64,
+    slots: usize,
+    words: Vec<u64>,
+    count: usize,
+}
+
+impl Bitmap {
+    pub fn new(start: u64, align: usize, slots: usize) -> Bitmap {
+        Bitmap {
+            start,
+            align: align as u64,
+            slots,
+            words: vec![0; slots.div_ceil(64)],
+            count: 0,
+        }
+    }
+
+    /// A bitmap with a slot for each of `offsets` into a buffer at `base`, which must be
+    /// `align` apart
+    pub fn for_offsets<I: Iterator<Item = usize>>(base: u64, align: usize, offsets: I) -> Bitmap {
+        let mut offsets = offsets.peekable();
+        let start = base + offsets.peek().copied().unwrap_or(0) as u64;
+        Bitmap::new(start, align, offsets.count())
+    }
+
+    pub fn set(&mut self, slot: usize) {
+        let (word, bit) = (slot / 64, 1 << (slot % 64));
+        if self.words[word] & bit == 0 {
+            self.words[word] |= bit;
+            self.count += 1;
+        }
+    }
+
+    fn addr(&self, slot: usize) -> u64 {
+        self.start + slot as u64 * self.align
+    }
+
+    /// Slots that are set, in order
+    fn set_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
+
+    fn addrs(&self) -> impl Iterator<Item = u64> + '_ {
+        self.set_slots().map(move |s| self.addr(s))
+    }
+
+    /// An empty bitmap over the same slots
+    fn cleared(&self) -> Bitmap {
+        Bitmap::new(self.start, self.align as usize, self.slots)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Repr {
+    /// Ascending addresses
+    List(Vec<u64>),
+    /// Bitmaps of ascending, disjoint ranges
+    Bitmaps(Vec<Bitmap>),
+}
+
+/// The addresses a search kept, in ascending order. Large sets, such as those of the
+/// first comparison of an unknown-value scan, are stored as a bitmap per chunk of memory
+/// and become a plain list once they are small enough.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ResultSet {
+    repr: Repr,
+    len: usize,
+}
+
+impl Default for ResultSet {
+    fn default() -> Self {
+        ResultSet::from(Vec::new())
+    }
+}
+
+impl From<Vec<u64>> for ResultSet {
+    fn from(addrs: Vec<u64>) -> Self {
+        ResultSet {
+            len: addrs.len(),
+            repr: Repr::List(addrs),
+        }
+    }
+}
+
+impl fmt::Debug for ResultSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.iter().map(|a| format!("{:X}", a)))
+            .finish()
+    }
+}
+
+impl PartialEq<Vec<u64>> for ResultSet {
+    fn eq(&self, other: &Vec<u64>) -> bool {
+        self.len == other.len() && self.iter().eq(other.iter().copied())
+    }
+}
+
+impl ResultSet {
+    /// Gather bitmaps in address order, as a list if they hold few enough results
+    pub fn from_bitmaps(bitmaps: Vec<Bitmap>) -> ResultSet {
+        let bitmaps: Vec<Bitmap> = bitmaps.into_iter().filter(|b| b.count > 0).collect();
+        let mut set = ResultSet {
+            len: bitmaps.iter().map(|b| b.count).sum(),
+            repr: Repr::Bitmaps(bitmaps),
+        };
+        set.compact();
+        set
+    }
+
+    /// Switch to an explicit list once there are few enough results
+    fn compact(&mut self) {
+        if let Repr::Bitmaps(ref bitmaps) = self.repr {
+            if self.len <= LIST_LIMIT {
+                self.repr = Repr::List(bitmaps.iter().flat_map(Bitmap::addrs).collect());
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the results are held as bitmaps rather than a list
+    pub fn is_compact(&self) -> bool {
+        matches!(self.repr, Repr::Bitmaps(_))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        let (list, bitmaps): (&[u64], &[Bitmap]) = match &self.repr {
+            Repr::List(list) => (list, &[]),
+            Repr::Bitmaps(bitmaps) => (&[], bitmaps),
+        };
+        list.iter()
+            .copied()
+            .chain(bitmaps.iter().flat_map(Bitmap::addrs))
+    }
+
+    /// The `idx`th result
+    pub fn get(&self, idx: usize) -> Option<u64> {
+        match &self.repr {
+            Repr::List(list) => list.get(idx).copied(),
+            Repr::Bitmaps(bitmaps) => {
+                let mut idx = idx;
+                for b in bitmaps {
+                    if idx < b.count {
+                        return b.set_slots().nth(idx).map(|s| b.addr(s));
+                    }
+                    idx -= b.count;
+                }
+                None
+            }
+        }
+    }
+
+    pub fn contains(&self, addr: u64) -> bool {
+        match &self.repr {
+            Repr::List(list) => list.binary_search(&addr).is_ok(),
+            Repr::Bitmaps(bitmaps) => {
+                let idx = bitmaps.partition_point(|b| b.start <= addr);
+                bitmaps[..idx].last().is_some_and(|b| {
+                    let off = addr - b.start;
+                    let slot = (off / b.align) as usize;
+                    off.is_multiple_of(b.align)
+                        && slot < b.slots
+                        && b.words[slot / 64] & (1 << (slot % 64)) != 0
+                })
+            }
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u64> {
+        self.iter().collect()
+    }
+
+    /// Keep the results `keep` accepts, passing the index of each one dropped to
+    /// `dropped` in ascending order. Returns how many were dropped.
+    pub fn retain<F, D>(&mut self, mut keep: F, mut dropped: D) -> usize
+    where
+        F: FnMut(u64) -> bool,
+        D: FnMut(usize),
+    {
+        let mut idx = 0;
+        let mut count = 0;
+        let mut check = |a: u64| {
+            let kept = keep(a);
+            if !kept {
+                dropped(idx);
+                count += 1;
+            }
+            idx += 1;
+            kept
+        };
+        match &mut self.repr {
+            Repr::List(list) => list.retain(|a| check(*a)),
+            Repr::Bitmaps(bitmaps) => {
+                for b in bitmaps.iter_mut() {
+                    let mut kept = b.cleared();
+                    for slot in b.set_slots() {
+                        if check(b.addr(slot)) {
+                            kept.set(slot);
+                        }
+                    }
+                    *b = kept;
+                }
+                bitmaps.retain(|b| b.count > 0);
+            }
+        }
+        self.len -= count;
+        self.compact();
+        count
+    }
+
+    /// Run `f` over every result on the thread pool, keeping the results it gives a value
+    /// for. Returns the kept results along with those values, in order.
+    pub fn par_filter_map<R, F>(&self, f: F) -> Result<(ResultSet, Vec<R>)>
+    where
+        R: Send,
+        F: Fn(usize, u64) -> Result<Option<R>> + Sync,
+    {
+        match &self.repr {
+            Repr::List(list) => {
+                let found = list
+                    .par_iter()
+                    .enumerate()
+                    .map(|(idx, a)| Ok(f(idx, *a)?.map(|r| (*a, r))))
+                    .collect::<Result<Vec<Option<(u64, R)>>>>()?;
+                let (addrs, values): (Vec<u64>, Vec<R>) = found.into_iter().flatten().unzip();
+                Ok((ResultSet::from(addrs), values))
+            }
+            Repr::Bitmaps(bitmaps) => {
+                // Index of the first result of each bitmap
+                let firsts: Vec<usize> = bitmaps
+                    .iter()
+                    .scan(0, |idx, b| {
+                        *idx += b.count;
+                        Some(*idx - b.count)
+                    })
+                    .collect();
+                let found = bitmaps
+                    .par_iter()
+                    .zip(firsts)
+                    .map(|(b, first)| {
+                        let mut kept = b.cleared();
+                        let mut values = Vec::new();
+                        for (i, slot) in b.set_slots().enumerate() {
+                            if let Some(r) = f(first + i, b.addr(slot))? {
+                                kept.set(slot);
+                                values.push(r);
+                            }
+                        }
+                        Ok((kept, values))
+                    })
+                    .collect::<Result<Vec<(Bitmap, Vec<R>)>>>()?;
+                let (kept, values): (Vec<Bitmap>, Vec<Vec<R>>) = found.into_iter().unzip();
+                Ok((
+                    ResultSet::from_bitmaps(kept),
+                    values.into_iter().flatten().collect(),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(start: u64, align: usize, slots: usize, set: &[usize]) -> Bitmap {
+        let mut b = Bitmap::new(start, align, slots);
+        set.iter().for_each(|s| b.set(*s));
+        b
+    }
+
+    #[test]
+    fn test_result_set() {
+        let many: Vec<usize> = (0..LIST_LIMIT + 2).collect();
+        let mut set = ResultSet::from_bitmaps(vec![
+            bitmap(0x1000, 4, 100, &[0, 63, 64, 99]),
+            bitmap(0x2000, 1, 10, &[]),
+            bitmap(0x10_0000, 1, LIST_LIMIT + 2, &many),
+        ]);
+        assert!(set.is_compact());
+        assert_eq!(set.len(), LIST_LIMIT + 6);
+        assert_eq!(set.get(2), Some(0x1100));
+        assert_eq!(set.get(4), Some(0x10_0000));
+        assert_eq!(set.get(LIST_LIMIT + 6), None);
+        assert!(set.contains(0x118C) && set.contains(0x10_0005));
+        assert!(!set.contains(0x1004) && !set.contains(0x1101) && !set.contains(0x2000));
+        assert_eq!(
+            set.iter().take(5).collect::<Vec<u64>>(),
+            vec![0x1000, 0x10FC, 0x1100, 0x118C, 0x10_0000]
+        );
+
+        let (low, values) = set
+            .par_filter_map(|idx, a| Ok(Some((idx, a)).filter(|_| a < 0x10_0004)))
+            .unwrap();
+        assert!(!low.is_compact());
+        assert_eq!(low.len(), 8);
+        assert_eq!(values[7], (7, 0x10_0003));
+
+        let mut dropped = Vec::new();
+        let count = set.retain(|a| a != 0x10FC && a < 0x10_0010, |idx| dropped.push(idx));
+        assert_eq!(count, LIST_LIMIT - 13);
+        assert_eq!(dropped.len(), count);
+        assert_eq!(dropped[..2], [1, 4 + 0x10]);
+        assert!(!set.is_compact());
+        assert_eq!(set.len(), 3 + 0x10);
+        let (odd, values) = set
+            .par_filter_map(|idx, a| Ok(Some(idx).filter(|_| a % 2 == 1)))
+            .unwrap();
+        assert_eq!(
+            odd.to_vec(),
+            (0x10_0001..0x10_0010).step_by(2).collect::<Vec<u64>>()
+        );
+        assert_eq!(values[0], 4);
+    }
+}
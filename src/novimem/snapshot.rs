@@ -0,0 +1,425 @@
+use super::{
+    error::{NoviMemError, Result},
+    region_reader::Chunk,
+};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    env, fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::FileExt,
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// Snapshot bytes kept in memory unless configured otherwise
+pub const DEFAULT_MEMORY_BUDGET: usize = 1 << 30;
+
+/// Tells generations of snapshots apart, and names their spill files
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// The compressed block this thread inflated last: (snapshots id, block address, bytes)
+    static INFLATED: RefCell<Option<(u64, u64, Vec<u8>)>> = const { RefCell::new(None) };
+}
+
+/// Where snapshots keep the memory they copy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotOptions {
+    /// Bytes of snapshot data held in memory, counting the previous snapshot.
+    /// Chunks beyond it are written to a spill file and mapped back in.
+    pub memory_budget: usize,
+    /// Deflate the chunks written to the spill file
+    pub compress: bool,
+    /// Directory for the spill file, the system temp directory if unset
+    pub spill_dir: Option<PathBuf>,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        SnapshotOptions {
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            compress: false,
+            spill_dir: None,
+        }
+    }
+}
+
+impl fmt::Display for SnapshotOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "budget {} MiB", self.memory_budget >> 20)?;
+        if self.compress {
+            write!(f, ", compressed")?;
+        }
+        match &self.spill_dir {
+            Some(dir) => write!(f, ", spill to {}", dir.display()),
+            None => write!(f, ", spill to {}", env::temp_dir().display()),
+        }
+    }
+}
+
+enum BlockData {
+    Memory(Vec<u8>),
+    /// Raw bytes at this offset of the spill file
+    Spilled(usize),
+    /// Deflated bytes of the spill file
+    Compressed {
+        offset: usize,
+        len: usize,
+    },
+}
+
+/// One chunk of a snapshot
+pub(crate) struct Block {
+    addr: u64,
+    len: usize,
+    data: BlockData,
+}
+
+impl Block {
+    fn end(&self) -> u64 {
+        self.addr + self.len as u64
+    }
+}
+
+/// A readable run of a region, made of the chunks it was read in
+pub(crate) struct Run {
+    pub region_key: u64,
+    len: usize,
+    blocks: Vec<Block>,
+}
+
+impl Run {
+    pub fn end(&self) -> u64 {
+        self.region_key + self.len as u64
+    }
+
+    /// Address range of each chunk
+    pub fn block_ranges(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.blocks.iter().map(|b| (b.addr, b.end()))
+    }
+}
+
+/// A copy of the scanned memory, in address order
+pub(crate) struct Snapshots {
+    runs: Vec<Run>,
+    spill: Option<Mmap>,
+    id: u64,
+}
+
+impl Default for Snapshots {
+    fn default() -> Self {
+        Snapshots {
+            runs: Vec::new(),
+            spill: None,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl Snapshots {
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    pub fn runs(&self) -> &[Run] {
+        &self.runs
+    }
+
+    pub fn retain<F: FnMut(&Run) -> bool>(&mut self, keep: F) {
+        self.runs.retain(keep)
+    }
+
+    /// Bytes held in memory and in the spill file
+    pub fn usage(&self) -> (usize, usize) {
+        let memory = self
+            .runs
+            .iter()
+            .flat_map(|r| &r.blocks)
+            .filter(|b| matches!(b.data, BlockData::Memory(_)))
+            .map(|b| b.len)
+            .sum();
+        (memory, self.spill.as_ref().map_or(0, |m| m.len()))
+    }
+
+    /// The `size` bytes at `addr`, if one run holds all of them
+    pub fn bytes(&self, addr: u64, size: usize) -> Result<Option<Cow<'_, [u8]>>> {
+        let idx = self.runs.partition_point(|r| r.end() <= addr);
+        let run = match self.runs.get(idx) {
+            Some(run) => run,
+            None => return Ok(None),
+        };
+        let end = addr + size as u64;
+        if addr < run.region_key || end > run.end() {
+            return Ok(None);
+        }
+        self.range(run, addr, end).map(Some)
+    }
+
+    /// The bytes of `run` from `start` to `end`, inflating or joining chunks as needed
+    pub fn range<'a>(&'a self, run: &'a Run, start: u64, end: u64) -> Result<Cow<'a, [u8]>> {
+        let first = run.blocks.partition_point(|b| b.end() <= start);
+        let blocks = run.blocks[first..].iter().take_while(|b| b.addr < end);
+        let slice = |b: &Block| {
+            let lo = start.max(b.addr) - b.addr;
+            let hi = end.min(b.end()) - b.addr;
+            lo as usize..hi as usize
+        };
+        let block = &run.blocks[first];
+        if end <= block.end() {
+            match &block.data {
+                BlockData::Memory(data) => return Ok(Cow::Borrowed(&data[slice(block)])),
+                BlockData::Spilled(offset) => {
+                    let data = self.spilled(*offset, block.len)?;
+                    return Ok(Cow::Borrowed(&data[slice(block)]));
+                }
+                BlockData::Compressed { .. } => {}
+            }
+        }
+        let mut buf = Vec::with_capacity((end - start) as usize);
+        for b in blocks {
+            self.with_block(b, |data| buf.extend_from_slice(&data[slice(b)]))?;
+        }
+        Ok(Cow::Owned(buf))
+    }
+
+    /// `len` bytes of the spill file at `offset`
+    fn spilled(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        self.spill
+            .as_ref()
+            .and_then(|spill| spill.get(offset..offset + len))
+            .ok_or_else(|| {
+                NoviMemError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "snapshot spill file is missing data",
+                ))
+            })
+    }
+
+    fn with_block<R, F: FnOnce(&[u8]) -> R>(&self, block: &Block, f: F) -> Result<R> {
+        match block.data {
+            BlockData::Memory(ref data) => Ok(f(data)),
+            BlockData::Spilled(offset) => Ok(f(self.spilled(offset, block.len)?)),
+            BlockData::Compressed { offset, len } => INFLATED.with(|cache| {
+                let mut cache = cache.borrow_mut();
+                let cached =
+                    matches!(&*cache, Some((id, addr, _)) if *id == self.id && *addr == block.addr);
+                if !cached {
+                    // Forget the old block first, so a failure cannot leave it posing as this one
+                    *cache = None;
+                    let mut data = Vec::with_capacity(block.len);
+                    DeflateDecoder::new(self.spilled(offset, len)?).read_to_end(&mut data)?;
+                    if data.len() != block.len {
+                        return Err(NoviMemError::Io(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "snapshot chunk inflated to the wrong size",
+                        )));
+                    }
+                    *cache = Some((self.id, block.addr, data));
+                }
+                Ok(f(&cache.as_ref().unwrap().2))
+            }),
+        }
+    }
+}
+
+struct Spill {
+    file: File,
+    len: usize,
+}
+
+/// Stores the chunks of a new snapshot as they are read, in memory while the budget
+/// lasts and in a spill file after that
+pub(crate) struct SnapshotWriter<'a> {
+    options: &'a SnapshotOptions,
+    budget: usize,
+    in_memory: AtomicUsize,
+    spill: Mutex<Option<Spill>>,
+    id: u64,
+}
+
+impl<'a> SnapshotWriter<'a> {
+    /// A writer whose memory budget is what `previous` leaves of the configured one
+    pub fn new(options: &'a SnapshotOptions, previous: &Snapshots) -> SnapshotWriter<'a> {
+        SnapshotWriter {
+            options,
+            budget: options.memory_budget.saturating_sub(previous.usage().0),
+            in_memory: AtomicUsize::new(0),
+            spill: Mutex::new(None),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Keep `chunk`, returning it as a block along with the start of its region
+    pub fn store(&self, chunk: Chunk) -> Result<(u64, Block)> {
+        let len = chunk.data.len();
+        let data = if self.in_memory.fetch_add(len, Ordering::Relaxed) + len <= self.budget {
+            BlockData::Memory(chunk.data)
+        } else {
+            self.in_memory.fetch_sub(len, Ordering::Relaxed);
+            if self.options.compress {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+                encoder.write_all(&chunk.data)?;
+                let deflated = encoder.finish()?;
+                BlockData::Compressed {
+                    offset: self.write_spill(&deflated)?,
+                    len: deflated.len(),
+                }
+            } else {
+                BlockData::Spilled(self.write_spill(&chunk.data)?)
+            }
+        };
+        let block = Block {
+            addr: chunk.addr,
+            len,
+            data,
+        };
+        Ok((chunk.region_start, block))
+    }
+
+    /// Append `bytes` to the spill file, returning where they start
+    fn write_spill(&self, bytes: &[u8]) -> Result<usize> {
+        let mut spill = self.spill.lock().unwrap();
+        if spill.is_none() {
+            let dir = self.options.spill_dir.clone().unwrap_or_else(env::temp_dir);
+            let path = dir.join(format!("novimem-{}-{}.snap", process::id(), self.id));
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            // The open file outlives its name, and nothing is left behind when it closes
+            fs::remove_file(&path)?;
+            *spill = Some(Spill { file, len: 0 });
+        }
+        let spill = spill.as_mut().unwrap();
+        let offset = spill.len;
+        spill.file.write_all_at(bytes, offset as u64)?;
+        spill.len += bytes.len();
+        Ok(offset)
+    }
+
+    /// Join the blocks, in address order, into the runs of the new snapshot
+    pub fn finish(self, blocks: Vec<(u64, Block)>) -> Result<Snapshots> {
+        let mut runs: Vec<Run> = Vec::new();
+        for (region_start, block) in blocks {
+            match runs.last_mut() {
+                Some(r) if r.region_key >= region_start && r.end() == block.addr => {
+                    r.len += block.len;
+                    r.blocks.push(block);
+                }
+                _ => runs.push(Run {
+                    region_key: block.addr,
+                    len: block.len,
+                    blocks: vec![block],
+                }),
+            }
+        }
+        let spill = match self.spill.into_inner().unwrap() {
+            // Only this writer ever had the unlinked file, so the map cannot change under us
+            Some(spill) => Some(unsafe { Mmap::map(&spill.file)? }),
+            None => None,
+        };
+        Ok(Snapshots {
+            runs,
+            spill,
+            id: self.id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::novimem::{
+        backend::mock::MockBackend, region_reader::PAGE_SIZE, value::DataType, NoviMem, SearchType,
+    };
+
+    /// Results and values of the same comparisons under `options`, with what the last
+    /// snapshot used
+    fn compare(options: SnapshotOptions) -> (Vec<u64>, Vec<String>, (usize, usize)) {
+        let mut backend = MockBackend::new();
+        let heap: Vec<u8> = (0..4 * PAGE_SIZE as usize)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        backend.add_region("[heap]", 0x10000, heap);
+        let mut m = NoviMem::with_backend(Box::new(backend), String::from("mock")).unwrap();
+        m.set_chunk_size(PAGE_SIZE as usize);
+        m.set_snapshot_options(options);
+        m.init_snapshots(DataType::U32, 1).unwrap();
+        // Values across the boundaries of chunks kept in memory, spilled or both
+        m.setval(0x10FFE, &7u32.to_le_bytes()).unwrap();
+        m.setval(0x12FFE, &7u32.to_le_bytes()).unwrap();
+        m.take_snapshots(Some(SearchType::Changed)).unwrap();
+        m.setval(0x12FFE, &9u32.to_le_bytes()).unwrap();
+        m.take_snapshots(Some(SearchType::Increased)).unwrap();
+        let values = (0..m.results().len())
+            .map(|i| m.result_value(i).unwrap().to_string())
+            .collect();
+        (m.results().to_vec(), values, m.snapshot_usage())
+    }
+
+    #[test]
+    fn test_spilled_snapshots() {
+        let (results, values, usage) = compare(SnapshotOptions::default());
+        assert_eq!(results, vec![0x12FFB, 0x12FFC, 0x12FFD, 0x12FFE]);
+        assert_eq!(usage, (4 * PAGE_SIZE as usize, 0));
+
+        for compress in [false, true].iter() {
+            for pages in 0..3 {
+                let options = SnapshotOptions {
+                    memory_budget: pages * PAGE_SIZE as usize,
+                    compress: *compress,
+                    spill_dir: None,
+                };
+                let (spilled, spilled_values, (memory, disk)) = compare(options);
+                assert_eq!(spilled, results);
+                assert_eq!(spilled_values, values);
+                // The previous snapshot holds on to its share of the budget
+                assert!(memory <= pages * PAGE_SIZE as usize);
+                assert!(disk > 0 && (*compress || memory + disk == 4 * PAGE_SIZE as usize));
+            }
+        }
+    }
+
+    #[test]
+    fn test_unreadable_spill() {
+        let snapshots = |data: &[u8]| {
+            let mut spill = memmap2::MmapMut::map_anon(data.len().max(1)).unwrap();
+            spill[..data.len()].copy_from_slice(data);
+            let block = Block {
+                addr: 0x1000,
+                len: 0x10,
+                data: BlockData::Compressed {
+                    offset: 0,
+                    len: data.len(),
+                },
+            };
+            Snapshots {
+                runs: vec![Run {
+                    region_key: 0x1000,
+                    len: 0x10,
+                    blocks: vec![block],
+                }],
+                spill: Some(spill.make_read_only().unwrap()),
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            }
+        };
+        // Garbage, and a valid stream of the wrong length, are errors rather than panics
+        assert!(snapshots(&[0xFF; 8]).bytes(0x1000, 4).is_err());
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&[1; 8]).unwrap();
+        let short = snapshots(&encoder.finish().unwrap());
+        assert!(short.bytes(0x1000, 4).is_err());
+        assert!(short.bytes(0x2000, 4).unwrap().is_none());
+    }
+}
@@ -0,0 +1,171 @@
+use super::error::{NoviMemError, Result};
+use regex::bytes::{Regex, RegexBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    pub fn parse(name: &str) -> Option<Encoding> {
+        match name {
+            "utf8" | "u8" => Some(Encoding::Utf8),
+            "utf16" | "utf16le" | "u16" | "u16le" => Some(Encoding::Utf16Le),
+            "utf16be" | "u16be" => Some(Encoding::Utf16Be),
+            _ => None,
+        }
+    }
+
+    /// Bytes per code unit
+    pub fn unit(self) -> usize {
+        match self {
+            Encoding::Utf8 => 1,
+            Encoding::Utf16Le | Encoding::Utf16Be => 2,
+        }
+    }
+
+    pub fn encode(self, s: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => s.as_bytes().to_vec(),
+            Encoding::Utf16Le => s.encode_utf16().flat_map(|u| u.to_le_bytes()).collect(),
+            Encoding::Utf16Be => s.encode_utf16().flat_map(|u| u.to_be_bytes()).collect(),
+        }
+    }
+
+    /// Decode as much of `buf` as possible, replacing invalid sequences
+    pub fn decode(self, buf: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(buf).to_string(),
+            _ => {
+                let units: Vec<u16> = buf
+                    .chunks_exact(2)
+                    .map(|c| match self {
+                        Encoding::Utf16Be => u16::from_be_bytes([c[0], c[1]]),
+                        _ => u16::from_le_bytes([c[0], c[1]]),
+                    })
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+        }
+    }
+}
+
+/// A text string to look for in memory
+#[derive(Debug, Clone)]
+pub struct TextSearch {
+    pub text: String,
+    pub encoding: Encoding,
+    pub case_insensitive: bool,
+    /// Only match strings that end right after `text`
+    pub null_terminated: bool,
+}
+
+impl TextSearch {
+    pub fn new(text: &str, encoding: Encoding) -> TextSearch {
+        TextSearch {
+            text: text.to_string(),
+            encoding,
+            case_insensitive: false,
+            null_terminated: false,
+        }
+    }
+
+    /// Length in bytes of an exact match, including the terminator
+    pub fn encoded_len(&self) -> usize {
+        self.encoding.encode(&self.text).len()
+            + if self.null_terminated {
+                self.encoding.unit()
+            } else {
+                0
+            }
+    }
+
+    pub fn to_regex(&self) -> Result<Regex> {
+        if self.text.is_empty() {
+            return Err(NoviMemError::Parse("empty search string".to_string()));
+        }
+        let mut valstr = String::new();
+        let escape =
+            |bytes: Vec<u8>| -> String { bytes.iter().map(|b| format!("\\x{:02x}", b)).collect() };
+        for c in self.text.chars() {
+            let mut variants = vec![c];
+            if self.case_insensitive {
+                // Only case mappings to a single character keep the encoded length predictable
+                let lower: Vec<char> = c.to_lowercase().collect();
+                let upper: Vec<char> = c.to_uppercase().collect();
+                for v in [lower, upper].iter().filter(|v| v.len() == 1) {
+                    if !variants.contains(&v[0]) {
+                        variants.push(v[0]);
+                    }
+                }
+            }
+            let alts: Vec<String> = variants
+                .iter()
+                .map(|v| escape(self.encoding.encode(&v.to_string())))
+                .collect();
+            valstr.push_str(&format!("(?:{})", alts.join("|")));
+        }
+        if self.null_terminated {
+            valstr.push_str(&escape(vec![0; self.encoding.unit()]));
+        }
+        let mut builder = RegexBuilder::new(&valstr);
+        builder
+            .unicode(false)
+            .dot_matches_new_line(true)
+            .case_insensitive(false);
+        builder
+            .build()
+            .map_err(|e| NoviMemError::Parse(e.to_string()))
+    }
+}
+
+/// Render `buf` as text, showing the `len` bytes at `hit` between brackets
+/// and unprintable characters as '.'
+pub fn context(encoding: Encoding, buf: &[u8], hit: usize, len: usize) -> String {
+    let clean = |b: &[u8]| -> String {
+        encoding
+            .decode(b)
+            .chars()
+            .map(|c| if c.is_control() { '.' } else { c })
+            .collect()
+    };
+    let end = (hit + len).min(buf.len());
+    format!(
+        "{}[{}]{}",
+        clean(&buf[..hit]),
+        clean(&buf[hit..end]),
+        clean(&buf[end..])
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_regex() {
+        let mut search = TextSearch::new("Hero", Encoding::Utf16Le);
+        search.case_insensitive = true;
+        let re = search.to_regex().unwrap();
+        let mem = Encoding::Utf16Le.encode("name=hERO!");
+        let found: Vec<usize> = re.find_iter(&mem).map(|m| m.start()).collect();
+        assert_eq!(found, vec![10]);
+
+        search.null_terminated = true;
+        assert_eq!(search.encoded_len(), 10);
+        assert!(search.to_regex().unwrap().find(&mem).is_none());
+
+        let re = TextSearch::new("Hero", Encoding::Utf8).to_regex().unwrap();
+        assert!(re.find(b"the hero").is_none());
+        assert_eq!(
+            Encoding::Utf16Be.decode(&Encoding::Utf16Be.encode("ok")),
+            "ok"
+        );
+        assert_eq!(
+            context(Encoding::Utf8, b"ab\x01Hero\x00x", 3, 4),
+            "ab.[Hero].x"
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use super::error::{NoviMemError, Result};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt};
+
+/// The numeric types values can be scanned as, all little-endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DataType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+}
+
+/// A decoded value. Integers of every width fit in an `i128`, so they compare without casts.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    Int(i128),
+    Float(f64),
+}
+
+macro_rules! le_bytes {
+    ($type: ty, $bytes: ident) => {{
+        let mut arr = [0u8; std::mem::size_of::<$type>()];
+        arr.copy_from_slice(&$bytes[..std::mem::size_of::<$type>()]);
+        <$type>::from_le_bytes(arr)
+    }};
+}
+
+impl DataType {
+    /// Parse a type name as used by the REPL (`u32`, `i`, `f64`, ...)
+    pub fn parse(name: &str) -> Option<DataType> {
+        match name {
+            "b" | "u8" => Some(DataType::U8),
+            "i8" => Some(DataType::I8),
+            "us" | "u16" => Some(DataType::U16),
+            "s" | "i16" => Some(DataType::I16),
+            "u" | "u32" => Some(DataType::U32),
+            "i" | "i32" => Some(DataType::I32),
+            "u64" => Some(DataType::U64),
+            "i64" => Some(DataType::I64),
+            "f" | "f32" => Some(DataType::F32),
+            "f64" => Some(DataType::F64),
+            _ => None,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            DataType::U8 | DataType::I8 => 1,
+            DataType::U16 | DataType::I16 => 2,
+            DataType::U32 | DataType::I32 | DataType::F32 => 4,
+            DataType::U64 | DataType::I64 | DataType::F64 => 8,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, DataType::F32 | DataType::F64)
+    }
+
+    /// Relative rounding error of a float type, nothing for integers
+    pub fn epsilon(self) -> f64 {
+        match self {
+            DataType::F32 => f32::EPSILON as f64,
+            DataType::F64 => f64::EPSILON,
+            _ => 0.0,
+        }
+    }
+
+    /// Decode the first `size()` bytes of `bytes`
+    pub fn decode(self, bytes: &[u8]) -> Value {
+        match self {
+            DataType::U8 => Value::Int(bytes[0] as i128),
+            DataType::I8 => Value::Int(bytes[0] as i8 as i128),
+            DataType::U16 => Value::Int(le_bytes!(u16, bytes) as i128),
+            DataType::I16 => Value::Int(le_bytes!(i16, bytes) as i128),
+            DataType::U32 => Value::Int(le_bytes!(u32, bytes) as i128),
+            DataType::I32 => Value::Int(le_bytes!(i32, bytes) as i128),
+            DataType::U64 => Value::Int(le_bytes!(u64, bytes) as i128),
+            DataType::I64 => Value::Int(le_bytes!(i64, bytes) as i128),
+            DataType::F32 => Value::Float(le_bytes!(f32, bytes) as f64),
+            DataType::F64 => Value::Float(le_bytes!(f64, bytes)),
+        }
+    }
+
+    /// Encode `value` as this type, truncating integers that do not fit
+    pub fn encode(self, value: Value) -> Vec<u8> {
+        let (i, f) = match value {
+            Value::Int(i) => (i, i as f64),
+            Value::Float(f) => (f as i128, f),
+        };
+        match self {
+            DataType::U8 | DataType::I8 => vec![i as u8],
+            DataType::U16 | DataType::I16 => (i as u16).to_le_bytes().to_vec(),
+            DataType::U32 | DataType::I32 => (i as u32).to_le_bytes().to_vec(),
+            DataType::U64 | DataType::I64 => (i as u64).to_le_bytes().to_vec(),
+            DataType::F32 => (f as f32).to_le_bytes().to_vec(),
+            DataType::F64 => f.to_le_bytes().to_vec(),
+        }
+    }
+
+    /// Parse a user-supplied value of this type. Integers may be given in hex with a `0x` prefix.
+    /// Floats are rounded to this type, so they compare equal to the same value in memory.
+    pub fn parse_value(self, s: &str) -> Result<Value> {
+        let err = || NoviMemError::Parse(format!("'{}' is not a valid {} value", s, self));
+        match self {
+            DataType::F32 => {
+                return s
+                    .parse::<f32>()
+                    .map(|f| Value::Float(f as f64))
+                    .map_err(|_| err())
+            }
+            DataType::F64 => return s.parse::<f64>().map(Value::Float).map_err(|_| err()),
+            _ => {}
+        }
+        let (min, max) = self.int_range();
+        let val = parse_int(s).ok_or_else(err)?;
+        if val < min || val > max {
+            return Err(err());
+        }
+        Ok(Value::Int(val))
+    }
+
+    /// Smallest and largest value of an integer type
+    fn int_range(self) -> (i128, i128) {
+        match self {
+            DataType::U8 => (0, u8::MAX as i128),
+            DataType::I8 => (i8::MIN as i128, i8::MAX as i128),
+            DataType::U16 => (0, u16::MAX as i128),
+            DataType::I16 => (i16::MIN as i128, i16::MAX as i128),
+            DataType::U32 => (0, u32::MAX as i128),
+            DataType::I32 => (i32::MIN as i128, i32::MAX as i128),
+            DataType::U64 => (0, u64::MAX as i128),
+            _ => (i64::MIN as i128, i64::MAX as i128),
+        }
+    }
+
+    /// Parse a difference between two values of this type. Integer differences may be
+    /// negative and as large as the span of the type, but no larger.
+    pub fn parse_delta(self, s: &str) -> Result<Value> {
+        if self.is_float() {
+            return self.parse_value(s);
+        }
+        let (min, max) = self.int_range();
+        let span = max - min;
+        match parse_int(s) {
+            Some(val) if -span <= val && val <= span => Ok(Value::Int(val)),
+            Some(_) => Err(NoviMemError::Parse(format!(
+                "'{}' is more than a {} value can change by ({})",
+                s, self, span
+            ))),
+            None => Err(NoviMemError::Parse(format!(
+                "'{}' is not a valid {} difference",
+                s, self
+            ))),
+        }
+    }
+}
+
+/// Parse a decimal or `0x`-prefixed hex integer, optionally negative
+fn parse_int(s: &str) -> Option<i128> {
+    let (neg, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let magnitude = match digits.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => digits.parse::<i128>(),
+    }
+    .ok()?;
+    Some(if neg { -magnitude } else { magnitude })
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DataType::U8 => "u8",
+            DataType::I8 => "i8",
+            DataType::U16 => "u16",
+            DataType::I16 => "i16",
+            DataType::U32 => "u32",
+            DataType::I32 => "i32",
+            DataType::U64 => "u64",
+            DataType::I64 => "i64",
+            DataType::F32 => "f32",
+            DataType::F64 => "f64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Value {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Value::Int(i) => i as f64,
+            Value::Float(f) => f,
+        }
+    }
+
+    /// `self - other`; integer differences stay exact
+    pub fn delta(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => Value::Int(a - b),
+            (a, b) => Value::Float(a.as_f64() - b.as_f64()),
+        }
+    }
+
+    pub fn abs(self) -> Value {
+        match self {
+            Value::Int(i) => Value::Int(i.abs()),
+            Value::Float(f) => Value::Float(f.abs()),
+        }
+    }
+
+    /// Equality that forgives the rounding error of float arithmetic on `dtype` values as
+    /// large as `magnitude`, such as the two values a difference was taken between
+    pub fn approx_eq(self, other: Value, dtype: DataType, magnitude: f64) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (a, b) => {
+                let (a, b) = (a.as_f64(), b.as_f64());
+                // Each operand of the difference is off by up to half an ulp, and so is `other`
+                let scale = magnitude.abs().max(a.abs()).max(b.abs()).max(1.0);
+                (a - b).abs() <= 2.0 * dtype.epsilon() * scale
+            }
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+            (a, b) => a.as_f64().partial_cmp(&b.as_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// How close a float must be to the searched value to match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FloatMatch {
+    /// `|v - x| <= eps`
+    AbsEpsilon(f64),
+    /// `|v - x| <= eps * |x|`
+    RelEpsilon(f64),
+    /// `v` rounded to this many decimals equals `x`, like a UI showing "99.5"
+    Rounded(u32),
+    /// `v` truncated to this many decimals equals `x`
+    Truncated(u32),
+}
+
+impl FloatMatch {
+    /// Parse a mode name (`abs`, `rel`, `round`, `trunc`) and its parameter
+    pub fn parse(mode: &str, param: &str) -> Result<FloatMatch> {
+        let err = || NoviMemError::Parse(format!("'{}' is not a valid {} parameter", param, mode));
+        match mode {
+            "abs" => param.parse().map(FloatMatch::AbsEpsilon).map_err(|_| err()),
+            "rel" => param.parse().map(FloatMatch::RelEpsilon).map_err(|_| err()),
+            "round" => param.parse().map(FloatMatch::Rounded).map_err(|_| err()),
+            "trunc" => param.parse().map(FloatMatch::Truncated).map_err(|_| err()),
+            _ => Err(NoviMemError::Parse(format!(
+                "unknown float match mode '{}'",
+                mode
+            ))),
+        }
+    }
+
+    /// Whether `v`, a value of the float type `dtype`, matches `target`
+    pub fn matches(self, dtype: DataType, target: f64, v: f64) -> bool {
+        match self {
+            FloatMatch::AbsEpsilon(eps) => (v - target).abs() <= eps,
+            FloatMatch::RelEpsilon(eps) => (v - target).abs() <= eps * target.abs(),
+            FloatMatch::Rounded(decimals) => {
+                let scale = 10f64.powi(decimals as i32);
+                (v * scale).round() == (target * scale).round()
+            }
+            FloatMatch::Truncated(decimals) => {
+                let scale = 10f64.powi(decimals as i32);
+                // A float just short of a decimal (an f32 0.7 is stored as 0.699999988) must
+                // not truncate to the digit below, so push it away from zero by its type's
+                // rounding error
+                let scaled = v * scale;
+                let nudge = dtype.epsilon() * scaled.abs().max(1.0);
+                (scaled + nudge.copysign(scaled)).trunc() == (target * scale).round()
+            }
+        }
+    }
+}
+
+/// A condition a single value must satisfy to be kept by a value search
+#[derive(Debug, Clone, Copy)]
+pub enum ValueMatch {
+    Exact(Value),
+    /// A float close enough to the value
+    Approx(f64, FloatMatch),
+    /// Inclusive on both ends
+    Between(Value, Value),
+    Greater(Value),
+    GreaterOrEqual(Value),
+    Less(Value),
+    LessOrEqual(Value),
+}
+
+impl ValueMatch {
+    /// Build a match from a REPL operator (`between`, `>`, `>=`, `<`, `<=`, `=`, `~`) and its operands.
+    /// `~ <value> [abs|rel|round|trunc <n>]` defaults to rounding to the decimals given in `value`.
+    pub fn parse(dtype: DataType, op: &str, args: &[&str]) -> Result<ValueMatch> {
+        let arg = |i: usize| match args.get(i) {
+            Some(a) => dtype.parse_value(a),
+            None => Err(NoviMemError::Parse(format!(
+                "'{}' needs {} operand(s)",
+                op,
+                if op == "between" { 2 } else { 1 }
+            ))),
+        };
+        match op {
+            "between" => {
+                let (lo, hi) = (arg(0)?, arg(1)?);
+                if lo > hi {
+                    Ok(ValueMatch::Between(hi, lo))
+                } else {
+                    Ok(ValueMatch::Between(lo, hi))
+                }
+            }
+            ">" => Ok(ValueMatch::Greater(arg(0)?)),
+            ">=" => Ok(ValueMatch::GreaterOrEqual(arg(0)?)),
+            "<" => Ok(ValueMatch::Less(arg(0)?)),
+            "<=" => Ok(ValueMatch::LessOrEqual(arg(0)?)),
+            "=" | "==" => Ok(ValueMatch::Exact(arg(0)?)),
+            "~" => {
+                if !dtype.is_float() {
+                    return Err(NoviMemError::Parse(format!(
+                        "'~' only applies to float types, not {}",
+                        dtype
+                    )));
+                }
+                let target = match arg(0)? {
+                    Value::Float(f) => f,
+                    Value::Int(i) => i as f64,
+                };
+                let fmatch = match (args.get(1), args.get(2)) {
+                    (Some(mode), Some(param)) => FloatMatch::parse(mode, param)?,
+                    (Some(mode), None) => {
+                        return Err(NoviMemError::Parse(format!("'{}' needs a parameter", mode)))
+                    }
+                    _ => {
+                        FloatMatch::Rounded(args[0].split('.').nth(1).map_or(0, |d| d.len() as u32))
+                    }
+                };
+                Ok(ValueMatch::Approx(target, fmatch))
+            }
+            _ => Err(NoviMemError::Parse(format!("unknown operator '{}'", op))),
+        }
+    }
+
+    /// Whether `op` is an operator understood by `parse`
+    pub fn is_operator(op: &str) -> bool {
+        matches!(op, "between" | ">" | ">=" | "<" | "<=" | "=" | "==" | "~")
+    }
+
+    /// Whether `v`, decoded as `dtype`, satisfies the condition
+    pub fn matches(&self, dtype: DataType, v: Value) -> bool {
+        match *self {
+            ValueMatch::Exact(x) => v == x,
+            ValueMatch::Approx(x, fmatch) => match v {
+                Value::Float(f) => fmatch.matches(dtype, x, f),
+                Value::Int(_) => false,
+            },
+            ValueMatch::Between(lo, hi) => v >= lo && v <= hi,
+            ValueMatch::Greater(x) => v > x,
+            ValueMatch::GreaterOrEqual(x) => v >= x,
+            ValueMatch::Less(x) => v < x,
+            ValueMatch::LessOrEqual(x) => v <= x,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_float_match_modes() {
+        assert!(FloatMatch::AbsEpsilon(0.1).matches(DataType::F64, 99.5, 99.58));
+        assert!(!FloatMatch::AbsEpsilon(0.1).matches(DataType::F64, 99.5, 99.61));
+        assert!(FloatMatch::RelEpsilon(0.01).matches(DataType::F64, 100.0, 100.9));
+        assert!(!FloatMatch::RelEpsilon(0.01).matches(DataType::F64, 100.0, 101.1));
+        assert!(FloatMatch::Rounded(1).matches(DataType::F64, 99.5, 99.54));
+        assert!(!FloatMatch::Rounded(1).matches(DataType::F64, 99.5, 99.56));
+        assert!(FloatMatch::Truncated(1).matches(DataType::F64, 99.5, 99.59));
+        assert!(!FloatMatch::Truncated(1).matches(DataType::F64, 99.5, 99.49));
+        assert!(FloatMatch::Truncated(0).matches(DataType::F64, -3.0, -3.7));
+
+        // Values an f32 cannot hold exactly still truncate to the decimals they show
+        let stored = 0.7f32 as f64;
+        assert!(stored < 0.7);
+        assert!(FloatMatch::Truncated(1).matches(DataType::F32, 0.7, stored));
+        assert!(FloatMatch::Truncated(1).matches(DataType::F32, -0.7, -stored));
+        assert!(FloatMatch::Truncated(2).matches(DataType::F32, 12.34, 12.34f32 as f64));
+        assert!(!FloatMatch::Truncated(1).matches(DataType::F32, 0.7, 0.69f32 as f64));
+        // An f64 gets only its own, much smaller, allowance
+        assert!(!FloatMatch::Truncated(1).matches(DataType::F64, 0.7, 0.699_999_99));
+        assert!(FloatMatch::Truncated(1).matches(DataType::F64, 0.7, 0.7));
+        let m = ValueMatch::parse(DataType::F32, "~", &["0.7", "trunc", "1"]).unwrap();
+        assert!(m.matches(DataType::F32, DataType::F32.decode(&0.7f32.to_le_bytes())));
+    }
+
+    #[test]
+    fn test_f32_operands() {
+        let stored = |f: f32| DataType::F32.decode(&f.to_le_bytes());
+        let parse = |op: &str, args: &[&str]| ValueMatch::parse(DataType::F32, op, args).unwrap();
+        // Neither bound is exact in binary; both are compared as the f32 memory holds them
+        assert!(parse("=", &["0.1"]).matches(DataType::F32, stored(0.1)));
+        assert!(parse("between", &["0.1", "0.2"]).matches(DataType::F32, stored(0.2)));
+        assert!(parse("between", &["0.1", "0.2"]).matches(DataType::F32, stored(0.1)));
+        assert!(parse("<=", &["0.2"]).matches(DataType::F32, stored(0.2)));
+        assert!(!parse("<", &["0.2"]).matches(DataType::F32, stored(0.2)));
+        assert_eq!(DataType::F64.parse_value("0.1").unwrap(), Value::Float(0.1));
+    }
+
+    #[test]
+    fn test_parse_delta() {
+        assert_eq!(DataType::U8.parse_delta("-255").unwrap(), Value::Int(-255));
+        assert_eq!(DataType::I8.parse_delta("0xFF").unwrap(), Value::Int(255));
+        assert!(DataType::U8.parse_delta("100000").is_err());
+        assert!(DataType::I16.parse_delta("-65536").is_err());
+        assert_eq!(
+            DataType::U64.parse_delta("18446744073709551615").unwrap(),
+            Value::Int(u64::MAX as i128)
+        );
+        assert!(DataType::U64.parse_delta("18446744073709551616").is_err());
+        assert!(DataType::U32.parse_delta("six").is_err());
+        assert_eq!(DataType::F32.parse_delta("0.5").unwrap(), Value::Float(0.5));
+    }
+
+    #[test]
+    fn test_approx_parse() {
+        // Without a mode, round to as many decimals as were typed
+        match ValueMatch::parse(DataType::F32, "~", &["99.5"]).unwrap() {
+            ValueMatch::Approx(x, FloatMatch::Rounded(1)) => assert_eq!(x, 99.5),
+            other => panic!("unexpected {:?}", other),
+        }
+        let m = ValueMatch::parse(DataType::F64, "~", &["2", "abs", "0.5"]).unwrap();
+        assert!(m.matches(DataType::F64, Value::Float(2.4)));
+        assert!(!m.matches(DataType::F64, Value::Float(2.6)));
+        assert!(ValueMatch::parse(DataType::U32, "~", &["2"]).is_err());
+        assert!(ValueMatch::parse(DataType::F32, "~", &["2", "abs"]).is_err());
+        assert!(ValueMatch::parse(DataType::F32, "~", &["2", "near", "1"]).is_err());
+    }
+}